@@ -40,7 +40,8 @@ fn test_win_ratio_perf_small() {
         bet_this_round: HashMap::new(),
         rules: Vec::new(),
         variant: PokerVariantViewState {
-            use_from_hand: 2
+            use_from_hand: 2,
+            ranking: HandRanking::High,
         },
         current_turn: Some(0),
     };
@@ -96,7 +97,8 @@ fn test_win_ratio_perf_twos_wild() {
         bet_this_round: HashMap::new(),
         rules: SpecialCardGroup::all().into_iter().next().unwrap().cards,
         variant: PokerVariantViewState {
-            use_from_hand: 5
+            use_from_hand: 5,
+            ranking: HandRanking::High,
         },
         current_turn: Some(0),
     };
@@ -165,7 +167,8 @@ fn test_win_ratio_perf() {
         bet_this_round: HashMap::new(),
         rules: Vec::new(),
         variant: PokerVariantViewState {
-            use_from_hand: 2
+            use_from_hand: 2,
+            ranking: HandRanking::High,
         },
         current_turn: Some(0),
     };
@@ -197,7 +200,8 @@ fn test_replace(num_opponents: usize) {
         bet_this_round: HashMap::new(),
         rules: Vec::new(),
         variant: PokerVariantViewState {
-            use_from_hand: 5
+            use_from_hand: 5,
+            ranking: HandRanking::High,
         },
         current_turn: Some(0),
     };
@@ -41,9 +41,11 @@ fn main() {
         bet_this_round: HashMap::new(),
         rules: Vec::new(),
         variant: PokerVariantViewState {
-            use_from_hand: 5
+            use_from_hand: 5,
+            ranking: HandRanking::High,
         },
         current_turn: Some(0),
+        carried_pot: 0,
     };
     let resp = best_replace(&vs, 4);
     println!("{:?}", resp);
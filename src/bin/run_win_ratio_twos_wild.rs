@@ -63,9 +63,11 @@ fn main() {
         bet_this_round: HashMap::new(),
         rules: SpecialCardGroup::all().into_iter().next().unwrap().cards,
         variant: PokerVariantViewState {
-            use_from_hand: 5
+            use_from_hand: 5,
+            ranking: HandRanking::High,
         },
         current_turn: Some(0),
+        carried_pot: 0,
     };
 
     let r = win_ratio(&vs);
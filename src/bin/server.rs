@@ -21,7 +21,13 @@ async fn main() -> Result<(), PokerRoundError> {
     let table_rules = TableRules {
         ante: AnteRule::Ante(1),
         ante_name: "ante".to_string(),
-        min_bet: 1
+        min_bet: 1,
+        betting: BettingStructure::NoLimit,
+        rake: None,
+        straddle: None,
+        bomb_pot: None,
+        run_it: None,
+        all_in_pause_ms: 0,
     };
     GameServer::create_and_serve(table_rules).await;
 
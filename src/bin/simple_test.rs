@@ -35,7 +35,13 @@ async fn main() -> Result<(), PokerRoundError> {
     let table_rules = TableRules {
         ante: AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]),
         ante_name: "ante".to_string(),
-        min_bet: 1
+        min_bet: 1,
+        betting: BettingStructure::NoLimit,
+        rake: None,
+        straddle: None,
+        bomb_pot: None,
+        run_it: None,
+        all_in_pause_ms: 0,
     };
     let variant = seven_card_stud();
     let mut deck = Box::new(standard_deck().clone());
@@ -47,6 +53,7 @@ async fn main() -> Result<(), PokerRoundError> {
         players,
         None,
         table_rules,
+        HandExtras::default(),
         vec![],
         0
     ).await?;
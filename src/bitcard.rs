@@ -5,7 +5,7 @@ use ts_rs::{TS, export};
 use std::ops::Index;
 use std::iter::FromIterator;
 
-#[derive(Copy, Clone, Eq, Hash, PartialEq, Ord, PartialOrd, Serialize, Deserialize, TS)]
+#[derive(Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize, TS)]
 pub struct RankTuple {
     rank_field: u64,
     length: usize,
@@ -125,6 +125,19 @@ impl RankTuple {
     }
 }
 
+// Compares rank by rank starting from index 0, like a Vec would
+impl Ord for RankTuple {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl PartialOrd for RankTuple {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl CardTuple {
     pub fn new() -> CardTuple {
         CardTuple {
@@ -419,6 +432,12 @@ mod test {
         let expected = vec![3, 5, 9, 0, 2, NUM_RANKS];
         let result = expected.iter().copied().collect::<RankTuple>().iter().collect::<Vec<_>>();
         assert!(result == expected, "{:?}", result);
+
+        let high: RankTuple = vec![12, 4, 1].into();
+        let low: RankTuple = vec![11, 10, 2].into();
+        assert!(high > low);
+        let short: RankTuple = vec![12, 4].into();
+        assert!(high > short);
     }
 
     #[test]
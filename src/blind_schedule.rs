@@ -0,0 +1,234 @@
+use crate::game::*;
+use crate::table::*;
+
+use ts_rs::{TS, export};
+use serde::{Serialize, Deserialize};
+
+use std::time::Duration;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+pub enum LevelDuration {
+    Hands(usize),
+    Minutes(u32),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct BlindLevel {
+    // Without blinds, only the ante is collected
+    pub small_blind: Chips,
+    pub big_blind: Chips,
+    pub ante: Chips,
+    // The big blind pays `ante` once for the whole table
+    #[serde(default)]
+    pub big_blind_ante: bool,
+    pub duration: LevelDuration,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+pub enum ScheduleEntry {
+    Level(BlindLevel),
+    Break {
+        minutes: u32,
+    },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct BlindSchedule {
+    // The last level lasts forever
+    pub entries: Vec<ScheduleEntry>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct LevelViewState {
+    // Counts levels from 1, skipping breaks. A break shows the level before it
+    pub level: usize,
+    pub entry: ScheduleEntry,
+    pub hands_left: Option<usize>,
+    pub secs_left: Option<u64>,
+}
+
+impl BlindLevel {
+    pub fn ante_rule(&self) -> AnteRule {
+        let blinds = vec![Blind {
+            amount: self.small_blind,
+        }, Blind {
+            amount: self.big_blind,
+        }];
+        if self.big_blind > 0 && self.ante > 0 {
+            AnteRule::BlindsAnte {
+                blinds,
+                ante: self.ante,
+                big_blind_ante: self.big_blind_ante,
+            }
+        } else if self.big_blind > 0 {
+            AnteRule::Blinds(blinds)
+        } else {
+            AnteRule::Ante(self.ante)
+        }
+    }
+}
+
+impl BlindSchedule {
+    pub fn is_valid(&self) -> bool {
+        let valid_entry = |entry: &ScheduleEntry| match entry {
+            ScheduleEntry::Level(BlindLevel{small_blind, big_blind, ante, duration, ..}) => {
+                let valid_duration = match duration {
+                    LevelDuration::Hands(hands) => *hands > 0,
+                    LevelDuration::Minutes(minutes) => *minutes > 0,
+                };
+                valid_duration && *ante >= 0 && *small_blind >= 0 && *big_blind >= *small_blind
+                    && (*big_blind > 0 || *ante > 0)
+            },
+            ScheduleEntry::Break{minutes} => *minutes > 0,
+        };
+        self.entries.iter().all(valid_entry)
+            && matches!(self.entries.first(), Some(ScheduleEntry::Level(_)))
+            && matches!(self.entries.last(), Some(ScheduleEntry::Level(_)))
+    }
+
+    pub fn first_level(&self) -> &BlindLevel {
+        self.entries.iter().find_map(|e| match e {
+            ScheduleEntry::Level(level) => Some(level),
+            _ => None,
+        }).unwrap()
+    }
+}
+
+// Tracks where a table is in its schedule. Levels measured in hands count every hand
+// dealt since the level started
+#[derive(Clone, Debug)]
+pub struct ScheduleClock {
+    schedule: BlindSchedule,
+    idx: usize,
+    start_hand: usize,
+    start_time: Duration,
+}
+
+impl ScheduleClock {
+    pub fn new(schedule: BlindSchedule) -> ScheduleClock {
+        ScheduleClock {
+            schedule,
+            idx: 0,
+            start_hand: 0,
+            start_time: Duration::from_secs(0),
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.idx
+    }
+
+    pub fn current(&self) -> ScheduleEntry {
+        self.schedule.entries[self.idx]
+    }
+
+    fn ends_after(&self) -> (Option<usize>, Option<Duration>) {
+        match self.current() {
+            ScheduleEntry::Level(BlindLevel{duration: LevelDuration::Hands(hands), ..}) => (Some(hands), None),
+            ScheduleEntry::Level(BlindLevel{duration: LevelDuration::Minutes(minutes), ..}) |
+            ScheduleEntry::Break{minutes} => (None, Some(Duration::from_secs(minutes as u64 * 60))),
+        }
+    }
+
+    // Moves on to whichever entry is current when `hand` starts at `uptime`. Returns true if it changed
+    pub fn advance(&mut self, hand: usize, uptime: Duration) -> bool {
+        let start_idx = self.idx;
+        while self.idx + 1 < self.schedule.entries.len() {
+            match self.ends_after() {
+                (Some(hands), _) if hand >= self.start_hand + hands => {
+                    self.start_time = uptime;
+                },
+                // Timed levels end on the clock, even if the last hand ran over
+                (_, Some(length)) if uptime >= self.start_time + length => {
+                    self.start_time += length;
+                },
+                _ => break,
+            }
+            self.start_hand = hand;
+            self.idx += 1;
+        }
+        self.idx != start_idx
+    }
+
+    // Time left in the current break, if on one
+    pub fn break_left(&self, uptime: Duration) -> Option<Duration> {
+        match self.current() {
+            ScheduleEntry::Break{minutes} => Some((self.start_time + Duration::from_secs(minutes as u64 * 60)).saturating_sub(uptime)),
+            _ => None,
+        }
+    }
+
+    pub fn view(&self, hand: usize, uptime: Duration) -> LevelViewState {
+        let level = self.schedule.entries[..=self.idx].iter().filter(|e| matches!(e, ScheduleEntry::Level(_))).count();
+        let last = self.idx + 1 == self.schedule.entries.len();
+        let (hands, length) = if last { (None, None) } else { self.ends_after() };
+        LevelViewState {
+            level,
+            entry: self.current(),
+            hands_left: hands.map(|h| (self.start_hand + h).saturating_sub(hand)),
+            secs_left: length.map(|l| (self.start_time + l).saturating_sub(uptime).as_secs()),
+        }
+    }
+}
+
+mod test {
+    use crate::blind_schedule::*;
+
+    fn level(big_blind: Chips, duration: LevelDuration) -> ScheduleEntry {
+        ScheduleEntry::Level(BlindLevel {
+            small_blind: big_blind / 2,
+            big_blind,
+            ante: 0,
+            big_blind_ante: false,
+            duration,
+        })
+    }
+
+    #[test]
+    fn test_schedule_clock() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let schedule = BlindSchedule {
+            entries: vec![
+                level(2, LevelDuration::Hands(3)),
+                level(4, LevelDuration::Minutes(10)),
+                ScheduleEntry::Break{minutes: 5},
+                level(8, LevelDuration::Hands(1)),
+            ],
+        };
+        assert!(schedule.is_valid());
+        let mut clock = ScheduleClock::new(schedule);
+        assert!(!clock.advance(2, minutes(30)));
+        assert_eq!(clock.view(2, minutes(30)).hands_left, Some(1));
+        assert!(clock.advance(3, minutes(31)));
+        assert_eq!(clock.current(), level(4, LevelDuration::Minutes(10)));
+        assert_eq!(clock.view(5, minutes(35)), LevelViewState {
+            level: 2,
+            entry: level(4, LevelDuration::Minutes(10)),
+            hands_left: None,
+            secs_left: Some(360),
+        });
+        // The break starts when the level's time runs out, not when the next hand starts
+        assert!(clock.advance(9, minutes(43)));
+        assert_eq!(clock.break_left(minutes(43)), Some(minutes(3)));
+        assert_eq!(clock.view(9, minutes(43)).level, 2);
+        assert!(clock.advance(9, minutes(46)));
+        assert_eq!(clock.current(), level(8, LevelDuration::Hands(1)));
+        // The last level never ends
+        assert!(!clock.advance(100, minutes(500)));
+        assert_eq!(clock.view(100, minutes(500)).hands_left, None);
+    }
+
+    #[test]
+    fn test_schedule_invalid() {
+        assert!(!BlindSchedule{entries: vec![]}.is_valid());
+        assert!(!BlindSchedule{entries: vec![ScheduleEntry::Break{minutes: 5}, level(2, LevelDuration::Hands(1))]}.is_valid());
+        assert!(!BlindSchedule{entries: vec![level(2, LevelDuration::Hands(0))]}.is_valid());
+    }
+}
@@ -4,6 +4,7 @@ use crate::comb::*;
 use crate::game::*;
 use crate::gamestate::*;
 use crate::viewstate::*;
+use crate::icm::*;
 
 use rand::prelude::*;
 
@@ -15,16 +16,26 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 pub trait Bot: Send + Sync {
-    fn bet(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips) -> BetResp;
+    fn bet(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp;
     fn replace(&self, state: &PokerViewState, max_can_replace: usize) -> ReplaceResp {
         best_replace(state, max_can_replace)
     }
+    // Used at tournament tables, where chips are worth a share of `payouts` rather than their face value.
+    // `other_stacks` are the chips of the players still in at the tournament's other tables
+    fn bet_icm(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips, max_bet: Chips, _payouts: &[Chips], _other_stacks: &[Chips]) -> BetResp {
+        self.bet(state, call_amount, min_bet, max_bet)
+    }
 }
 
+// Looks up the stacks at a tournament's other tables each time the bot bets, since players move
+pub type OtherStacks = Box<dyn Fn() -> Vec<Chips> + Send + Sync>;
+
 pub struct BotInputSource {
     bot: Arc<dyn Bot>,
     viewstate_tx: watch::Sender<Option<PokerViewState>>,
     viewstate_rx: watch::Receiver<Option<PokerViewState>>,
+    payouts: Option<Vec<Chips>>,
+    other_stacks: OtherStacks,
 }
 
 #[derive(Clone, Copy, Debug, PartialOrd, PartialEq)]
@@ -38,18 +49,29 @@ impl BotInputSource {
     pub fn new(bot: Arc<dyn Bot>) -> BotInputSource {
         let (viewstate_tx, viewstate_rx) = watch::channel(None);
         BotInputSource {
-            bot, viewstate_tx, viewstate_rx
+            bot, viewstate_tx, viewstate_rx, payouts: None, other_stacks: Box::new(Vec::new)
+        }
+    }
+
+    pub fn for_tournament(bot: Arc<dyn Bot>, payouts: Vec<Chips>, other_stacks: OtherStacks) -> BotInputSource {
+        BotInputSource {
+            payouts: Some(payouts),
+            other_stacks,
+            ..BotInputSource::new(bot)
         }
     }
 }
 
 #[async_trait]
 impl PlayerInputSource for BotInputSource {
-    async fn bet(&self, call_amount: Chips, min_bet: Chips) -> BetResp {
+    async fn bet(&self, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp {
         let mstate = self.viewstate_rx.borrow();
         let state: &PokerViewState = mstate.as_ref().unwrap();
         tokio::task::block_in_place(move || {
-            self.bot.bet(state, call_amount, min_bet)
+            match &self.payouts {
+                Some(payouts) => self.bot.bet_icm(state, call_amount, min_bet, max_bet, payouts, &(self.other_stacks)()),
+                None => self.bot.bet(state, call_amount, min_bet, max_bet),
+            }
         })
     }
 
@@ -146,7 +168,7 @@ pub fn hands(cards: &[CardViewState]) -> Vec<CardTuple> {
     retval
 }
 
-pub fn bet_risk_factor(state: &PokerViewState, call_amount: Chips, min_bet: Chips, risk_factor: RiskFactor) -> BetResp {
+pub fn bet_risk_factor(state: &PokerViewState, call_amount: Chips, min_bet: Chips, max_bet: Chips, risk_factor: RiskFactor) -> BetResp {
     let bet_this_round = state.bet_this_round.get(&state.role).unwrap_or(&0);
 
     use RiskFactor::*;
@@ -159,26 +181,52 @@ pub fn bet_risk_factor(state: &PokerViewState, call_amount: Chips, min_bet: Chip
             }
         },
         PotRatio(ratio) => {
-            let max_bet = (state.pot() as f64 * ratio) as Chips;
-            if call_amount - bet_this_round > max_bet {
+            let risk_bet = (state.pot() as f64 * ratio) as Chips;
+            if call_amount - bet_this_round > risk_bet {
                 BetResp::Fold
-            } else if max_bet < min_bet {
+            } else if risk_bet < min_bet || max_bet < min_bet {
                 BetResp::Bet(std::cmp::min(call_amount, state.bettable_chips(state.role)))
             } else {
-                BetResp::Bet(std::cmp::min(max_bet + call_amount, state.bettable_chips(state.role)))
+                BetResp::Bet(std::cmp::min(risk_bet + call_amount, max_bet))
             }
         },
         CallAny => {
-            let max_bet = (state.pot() as f64 * 0.25) as Chips;
-            if max_bet < min_bet {
+            let risk_bet = (state.pot() as f64 * 0.25) as Chips;
+            if risk_bet < min_bet || max_bet < min_bet {
                 BetResp::Bet(std::cmp::min(call_amount, state.bettable_chips(state.role)))
             } else {
-                BetResp::Bet(std::cmp::min(max_bet + call_amount, state.bettable_chips(state.role)))
+                BetResp::Bet(std::cmp::min(risk_bet + call_amount, max_bet))
             }
         }
     }
 }
 
+// Decides a call that settles the hand, because either the bot or the player who bet is all in,
+// by ICM equity instead of chips. Returns None for any other bet
+pub fn icm_call_or_fold(state: &PokerViewState, call_amount: Chips, payouts: &[Chips], other_stacks: &[Chips], win_prob: f64) -> Option<BetResp> {
+    let bet_this_round = |role: &PlayerRole| state.bet_this_round.get(role).copied().unwrap_or(0);
+    let behind = |role: &PlayerRole| state.bettable_chips(*role) - bet_this_round(role);
+    let opponent = state.players.iter().filter(|(r, p)| **r != state.role && !p.folded).max_by_key(|(r, _)| bet_this_round(r)).map(|(r, _)| *r)?;
+    let call = std::cmp::min(call_amount - bet_this_round(&state.role), behind(&state.role));
+    if call <= 0 || (call < behind(&state.role) && behind(&opponent) > 0) {
+        return None;
+    }
+    let roles: Vec<PlayerRole> = state.players.keys().copied().collect();
+    let mut stacks: Vec<Chips> = roles.iter().map(behind).collect();
+    let index = |role: PlayerRole| roles.iter().position(|&r| r == role).unwrap();
+    // The part of the bet the bot can't cover goes back to the player who made it
+    let excess = std::cmp::max(0, bet_this_round(&opponent) - bet_this_round(&state.role) - call);
+    stacks[index(opponent)] += excess;
+    // The payouts are shared with everyone left in the tournament, not just this table
+    stacks.extend_from_slice(other_stacks);
+    let decision = call_or_fold(&stacks, payouts, index(state.role), index(opponent), call, state.pot() - excess, win_prob).ok()?;
+    if decision.call >= decision.fold {
+        Some(BetResp::Bet(bet_this_round(&state.role) + call))
+    } else {
+        Some(BetResp::Fold)
+    }
+}
+
 pub fn win_ratio(state: &PokerViewState) -> f64 {
     win_ratio_cards_left(state, standard_deck().raw.iter().copied().collect())
 }
@@ -253,7 +301,7 @@ pub fn win_ratio_cards_left(state: &PokerViewState, cards_left: CardSet) -> f64
 
             let player_combos = combinations(cards_left.iter(), max_player_hidden);
 
-            let my_best = best_hand_use_from_hand(state.variant.use_from_hand, my_hand, community, 5, &state.rules);
+            let my_best = best_hand_use_from_hand(state.variant.ranking, state.variant.use_from_hand, my_hand, community, 5, &state.rules);
             for player_combo in player_combos {
                 for (visible, hidden, roles) in &players {
                     total += *roles;
@@ -261,7 +309,7 @@ pub fn win_ratio_cards_left(state: &PokerViewState, cards_left: CardSet) -> f64
                     for card in &player_combo[..*hidden] {
                         player_hand.push(*card);
                     }
-                    let player_best = best_hand_use_from_hand(state.variant.use_from_hand, player_hand, community, 5, &state.rules);
+                    let player_best = best_hand_use_from_hand(state.variant.ranking, state.variant.use_from_hand, player_hand, community, 5, &state.rules);
                     if my_best > player_best {
                         won += *roles;
                     }
@@ -276,7 +324,7 @@ pub fn win_ratio_cards_left(state: &PokerViewState, cards_left: CardSet) -> f64
 pub fn best_replace(state: &PokerViewState, max_can_replace: usize) -> ReplaceResp {
     let player_hand = visible_cards(&my_hand(state));
     let community = visible_cards(&state.community_cards);
-    let start_str = best_hand_use_from_hand(state.variant.use_from_hand, player_hand, community, 5, &state.rules);
+    let start_str = best_hand_use_from_hand(state.variant.ranking, state.variant.use_from_hand, player_hand, community, 5, &state.rules);
     let cards_left = cards_left(state);
     let mut best_picked = Vec::new();
     let mut best = 0f64;
@@ -298,7 +346,7 @@ pub fn best_replace(state: &PokerViewState, max_can_replace: usize) -> ReplaceRe
                 for card in combo {
                     new_hand.push(card);
                 }
-                let st = best_hand_use_from_hand(state.variant.use_from_hand, new_hand, community, 5, &state.rules);
+                let st = best_hand_use_from_hand(state.variant.ranking, state.variant.use_from_hand, new_hand, community, 5, &state.rules);
                 if st > start_str {
                     good += 1;
                 } else if st < start_str {
@@ -329,6 +377,43 @@ mod test {
         })).collect()
     }
 
+    #[test]
+    fn test_icm_other_tables() {
+        let players = vec![(0, PlayerViewState {
+                chips: 1000,
+                total_bet: 0,
+                hand: Vec::new(),
+                folded: false,
+            }),
+            (1, PlayerViewState {
+                chips: 1000,
+                total_bet: 0,
+                hand: Vec::new(),
+                folded: false,
+            }),
+        ].into_iter().collect();
+
+        // The opponent shoved, and the bot has a coin flip
+        let vs = PokerViewState {
+            role: 0,
+            players,
+            community_cards: Vec::new(),
+            bet_this_round: vec![(1, 1000)].into_iter().collect(),
+            rules: Vec::new(),
+            variant: PokerVariantViewState {
+                use_from_hand: 0,
+                ranking: HandRanking::High,
+            },
+            current_turn: Some(0),
+            carried_pot: 0,
+        };
+
+        // Heads up for everything left, calling is worth as much as folding
+        assert_eq!(icm_call_or_fold(&vs, 1000, &[50, 30, 20], &[], 0.5), Some(BetResp::Bet(1000)));
+        // but with a player still in at another table, busting misses out on second place
+        assert_eq!(icm_call_or_fold(&vs, 1000, &[50, 30, 20], &[1000], 0.5), Some(BetResp::Fold));
+    }
+
     #[test]
     #[ignore]
     fn test_win_ratio() {
@@ -353,9 +438,11 @@ mod test {
             bet_this_round: HashMap::new(),
             rules: Vec::new(),
             variant: PokerVariantViewState {
-                use_from_hand: 2
+                use_from_hand: 2,
+                ranking: HandRanking::High,
             },
             current_turn: Some(0),
+            carried_pot: 0,
         };
 
         let r = win_ratio(&vs);
@@ -387,9 +474,11 @@ mod test {
             bet_this_round: HashMap::new(),
             rules: Vec::new(),
             variant: PokerVariantViewState {
-                use_from_hand: 2
+                use_from_hand: 2,
+                ranking: HandRanking::High,
             },
             current_turn: Some(0),
+            carried_pot: 0,
         };
 
         let r = win_ratio(&vs);
@@ -423,9 +512,11 @@ mod test {
             bet_this_round: HashMap::new(),
             rules: Vec::new(),
             variant: PokerVariantViewState {
-                use_from_hand: 5
+                use_from_hand: 5,
+                ranking: HandRanking::High,
             },
             current_turn: Some(0),
+            carried_pot: 0,
         };
 
         let r = win_ratio(&vs);
@@ -460,9 +551,11 @@ mod test {
             bet_this_round: HashMap::new(),
             rules: Vec::new(),
             variant: PokerVariantViewState {
-                use_from_hand: 5
+                use_from_hand: 5,
+                ranking: HandRanking::High,
             },
             current_turn: Some(0),
+            carried_pot: 0,
         };
         let resp = best_replace(&vs, 4);
         assert!(resp == vec![3,4], "{:?}", resp);
@@ -5,7 +5,7 @@ use crate::bot::*;
 pub struct BotAlwaysCall{}
 
 impl Bot for BotAlwaysCall {
-    fn bet(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips) -> BetResp {
+    fn bet(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips, _max_bet: Chips) -> BetResp {
         check_or_call_any(state, call_amount)
     }
 }
@@ -7,7 +7,7 @@ use crate::bot::*;
 pub struct BotEasy{}
 
 impl Bot for BotEasy {
-    fn bet(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips) -> BetResp {
+    fn bet(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp {
         let hand = my_hand(state);
         use RiskFactor::*;
 
@@ -17,8 +17,19 @@ impl Bot for BotEasy {
         } else if hand.len() + state.community_cards.len() < 5 {
             PotRatio(0.5f64)
         } else {
-            let best = best_hand(*hands(&hand).first().unwrap(), *hands(&state.community_cards).first().unwrap(), 5, &state.rules);
-            if best.kind >= Kind::FourKind(0) {
+            let best = best_hand_ranked(state.variant.ranking, *hands(&hand).first().unwrap(), *hands(&state.community_cards).first().unwrap(), 5, &state.rules);
+            if let Kind::Low(LowHand{kind: LowKind::NoPair, ranks}) = best.kind {
+                // Ranks are highest first
+                PotRatio(
+                    if ranks.get(0) <= 6 {
+                        1f64
+                    } else if ranks.get(0) <= 8 {
+                        0.5f64
+                    } else {
+                        0.125f64
+                    }
+                )
+            } else if best.kind >= Kind::FourKind(0) {
                 CallAny
             } else {
             PotRatio(
@@ -39,7 +50,7 @@ impl Bot for BotEasy {
                 }
             )}
         };
-        bet_risk_factor(state, call_amount, min_bet, risk_factor)
+        bet_risk_factor(state, call_amount, min_bet, max_bet, risk_factor)
     }
 }
 
@@ -7,21 +7,32 @@ use crate::bot::*;
 pub struct BotMedium{}
 
 impl Bot for BotMedium {
-    fn bet(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips) -> BetResp {
+    fn bet(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp {
+        self.bet_with_payouts(state, call_amount, min_bet, max_bet, None, &[])
+    }
+
+    fn bet_icm(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips, max_bet: Chips, payouts: &[Chips], other_stacks: &[Chips]) -> BetResp {
+        self.bet_with_payouts(state, call_amount, min_bet, max_bet, Some(payouts), other_stacks)
+    }
+}
+
+impl BotMedium {
+    pub fn new() -> BotMedium {
+        BotMedium{}
+    }
+
+    fn bet_with_payouts(&self, state: &PokerViewState, call_amount: Chips, min_bet: Chips, max_bet: Chips, payouts: Option<&[Chips]>, other_stacks: &[Chips]) -> BetResp {
         use RiskFactor::*;
 
         let risk_factor = if pocket_threes(state) {
             CallAny
         } else {
             let r = win_ratio(state);
+            if let Some(resp) = payouts.and_then(|payouts| icm_call_or_fold(state, call_amount, payouts, other_stacks, r)) {
+                return resp;
+            }
             PotRatio(r)
         };
-        bet_risk_factor(state, call_amount, min_bet, risk_factor)
-    }
-}
-
-impl BotMedium {
-    pub fn new() -> BotMedium {
-        BotMedium{}
+        bet_risk_factor(state, call_amount, min_bet, max_bet, risk_factor)
     }
 }
@@ -14,10 +14,69 @@ pub type Rank = usize;
 
 pub type StrengthRank = usize;
 
+// Lowball categories, best first
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, TS, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", content="data")]
+pub enum LowKind {
+    NoPair,
+    Pair,
+    TwoPair,
+    ThreeKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourKind,
+    StraightFlush,
+}
+
+// Ranks are grouped (pairs first) then highest first.
+// Orders so that the better (lower) hand is greater.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TS, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct LowHand {
+    pub kind: LowKind,
+    pub ranks: RankTuple,
+}
+
+impl Ord for LowHand {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.kind.cmp(&self.kind).then_with(|| other.ranks.cmp(&self.ranks))
+    }
+}
+
+impl PartialOrd for LowHand {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// The cards of different suits and ranks, highest rank first.
+// More cards is better, then lower ranks. Orders so that the better hand is greater.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TS, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct BadugiHand {
+    pub ranks: RankTuple,
+}
+
+impl Ord for BadugiHand {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.ranks.len().cmp(&other.ranks.len()).then_with(|| other.ranks.cmp(&self.ranks))
+    }
+}
+
+impl PartialOrd for BadugiHand {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, TS, Hash)]
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "kind", content="data")]
 pub enum Kind {
+    Low(LowHand),
+    Badugi(BadugiHand),
     HighCard(StrengthRank),
     Pair(StrengthRank),
     TwoPair {
@@ -56,6 +115,8 @@ impl std::fmt::Display for Kind {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         use Kind::*;
         match self {
+            Low(low) => write!(f, "{}", low)?,
+            Badugi(badugi) => write!(f, "{}", badugi)?,
             HighCard(r) => write!(f, "{} high", rank_name(r))?,
             Pair(r) => write!(f, "Pair of {}s", rank_name(r))?,
             TwoPair{high, low} => write!(f, "Two pair {}s over {}s", rank_name(high), rank_name(low))?,
@@ -72,6 +133,37 @@ impl std::fmt::Display for Kind {
     }
 }
 
+impl std::fmt::Display for LowHand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use LowKind::*;
+        let ranks = &self.ranks;
+        match self.kind {
+            NoPair if ranks.len() > 1 => write!(f, "{}-{} low", rank_name(&ranks.get(0)), rank_name(&ranks.get(1)))?,
+            NoPair => write!(f, "{} low", rank_name(&ranks.get(0)))?,
+            Pair => write!(f, "Low with a pair of {}s", rank_name(&ranks.get(0)))?,
+            TwoPair => write!(f, "Low with two pair {}s and {}s", rank_name(&ranks.get(0)), rank_name(&ranks.get(1)))?,
+            ThreeKind => write!(f, "Low with three {}s", rank_name(&ranks.get(0)))?,
+            Straight => write!(f, "Low with a straight, {} high", rank_name(&ranks.get(0)))?,
+            Flush => write!(f, "Low with a flush, {} high", rank_name(&ranks.get(0)))?,
+            StraightFlush => write!(f, "Low with a straight flush, {} high", rank_name(&ranks.get(0)))?,
+            FullHouse => write!(f, "Low with a full house")?,
+            FourKind => write!(f, "Low with four {}s", rank_name(&ranks.get(0)))?,
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for BadugiHand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        const COUNTS: [&str; 4] = ["No", "One", "Two", "Three"];
+        let high = rank_name(&self.ranks.get(0));
+        match self.ranks.len() {
+            4 => write!(f, "Badugi, {} high", high),
+            n => write!(f, "{} card hand, {} high", COUNTS[n], high),
+        }
+    }
+}
+
 impl std::fmt::Display for Card {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{:1}{:-2}", char::from_u32(match self.suit {
@@ -104,6 +196,12 @@ impl From<(usize, usize)> for Card {
 
 pub trait Deck {
     fn draw(&mut self) -> Result<Card, PokerRoundError>;
+    fn cards_left(&self) -> usize;
+
+    // Shuffles discards in under the cards left to deal
+    fn reshuffle(&mut self, _discards: Vec<Card>) -> Result<(), PokerRoundError> {
+        Err("This deck can't reshuffle discards".to_string())
+    }
 }
 
 pub trait Shuffleable {
@@ -127,6 +225,18 @@ impl Deck for VecDeck {
             Err("Ran out of cards in deck".to_string())
         }
     }
+
+    fn cards_left(&self) -> usize {
+        self.raw.len()
+    }
+
+    fn reshuffle(&mut self, mut discards: Vec<Card>) -> Result<(), PokerRoundError> {
+        discards.shuffle(&mut rand::thread_rng());
+        // Cards are dealt from the end
+        discards.append(&mut self.raw);
+        self.raw = discards;
+        Ok(())
+    }
 }
 
 impl Shuffleable for VecDeck {
@@ -49,15 +49,17 @@ impl CmdlineInputSource {
         return buf;
     }
 
-    fn bet_amount(&self, min_bet: Chips, call_amount: Chips) -> Option<Chips> {
-        let prompt = if call_amount == 0 {
-            "Bet amount?".to_string()
+    fn bet_amount(&self, min_bet: Chips, max_bet: Chips, call_amount: Chips) -> Option<Chips> {
+        let prompt = if min_bet == max_bet {
+            format!("Raise to {}?", max_bet)
+        } else if call_amount == 0 {
+            format!("Bet amount ({}-{})?", min_bet, max_bet)
         } else {
-            format!("Raise amount (>={} or all in)?", min_bet)
+            format!("Raise amount ({}-{} or all in)?", min_bet, max_bet)
         };
         if let Ok(v) = self.prompt(&prompt).parse::<Chips>() {
             if let Some(state) = self.viewstate() {
-                if let Err(reason) = state.valid_bet(min_bet, call_amount, v, state.role) {
+                if let Err(reason) = state.valid_bet(min_bet, max_bet, call_amount, v, state.role) {
                     println!("{}", reason);
                 } else {
                     return Some(v);
@@ -98,7 +100,7 @@ enum ReplaceChoice {
 
 #[async_trait]
 impl PlayerInputSource for CmdlineInputSource {
-    async fn bet(&self, call_amount: Chips, min_bet: Chips) -> BetResp {
+    async fn bet(&self, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp {
         use MenuChoice::*;
         let choices = vec![
             (CheckCall, if call_amount == 0 {
@@ -123,7 +125,7 @@ impl PlayerInputSource for CmdlineInputSource {
                     return BetResp::Fold;
                 },
                 Bet => {
-                    if let Some(value) = self.bet_amount(min_bet, call_amount) {
+                    if let Some(value) = self.bet_amount(min_bet, max_bet, call_amount) {
                         return BetResp::Bet(value);
                     }
                 }
@@ -11,6 +11,7 @@ use ts_rs::{TS, export};
 
 export! {
     BetResp,
+    ShowResp,
     Facing,
     PokerLogUpdate,
     CardViewState,
@@ -19,10 +20,41 @@ export! {
     PokerVariantViewState,
     PokerViewState,
     TableConfig,
+    ActionTimer,
+    ActionClock,
+    RebuyRules,
+    AddOn,
+    BuyInKind,
+    LedgerEntry,
+    PlayerBalance,
+    Transfer,
+    Settlement,
+    LevelDuration,
+    BlindLevel,
+    ScheduleEntry,
+    BlindSchedule,
+    LevelViewState,
+    PrizeStructure,
+    Standing,
+    TournamentConfig,
+    TournamentViewState,
+    IcmRequest,
+    IcmEquity,
+    IcmDecision,
     TableViewState,
     BetDiffKind,
     PokerVariantDesc,
     PokerVariants,
+    PokerVariant,
+    Round,
+    ActionOrder,
+    ReplaceRule,
+    SplitPot,
+    HandRanking,
+    LowKind,
+    LowHand,
+    BadugiHand,
+    PotWinners<PlayerId>,
     PokerVariantSelector,
     PokerViewDiff<PlayerId>,
     CardState,
@@ -47,6 +79,11 @@ export! {
     AnteRuleDesc,
     ServerTableParameters,
     TableRules,
+    BettingStructure,
+    Rake,
+    RakeEntry,
+    StraddleRule,
+    BombPotRule,
     AnteRule,
     Blind,
     TableEvent,
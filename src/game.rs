@@ -8,7 +8,7 @@ use ts_rs::{TS, export};
 use serde::{Serialize, Deserialize};
 use async_trait::async_trait;
 use tokio::sync::oneshot;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::collections::HashMap;
 
 pub type Chips = i32;
@@ -34,6 +34,16 @@ pub enum BetResp {
 
 pub type ReplaceResp = Vec<usize>;
 
+// What a player who won without a showdown turns up
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+pub enum ShowResp {
+    Muck,
+    One(usize),
+    All,
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub enum PlayerResp {
     Bet(BetResp),
@@ -75,13 +85,75 @@ pub struct DealersChoiceResp {
 
 #[async_trait]
 pub trait PlayerInputSource: Send + Sync {
-    async fn bet(&self, call_amount: Chips, min_bet: Chips) -> BetResp;
+    async fn bet(&self, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp;
     async fn replace(&self, max_can_replace: usize) -> ReplaceResp;
     async fn dealers_choice(&self, variants: Vec<PokerVariantDesc>) -> DealersChoiceResp;
+    // Asked before the cards are dealt whether to straddle for `amount`
+    async fn straddle(&self, _amount: Chips) -> bool {
+        false
+    }
+    // Asked when the players left are all in whether to deal the rest of the board `times` times
+    async fn run_it(&self, _times: usize) -> bool {
+        false
+    }
+    // Asked at showdown when the player can't win. Returns false to muck
+    async fn show_or_muck(&self) -> bool {
+        true
+    }
+    // Asked after winning without a showdown
+    async fn show_uncontested(&self, _num_cards: usize) -> ShowResp {
+        ShowResp::Muck
+    }
     fn update(&self, viewstate: PokerViewUpdate);
+    // Whether the player has stopped acting for themselves, e.g. by running out of time
+    fn sitting_out(&self) -> bool {
+        false
+    }
+    fn set_sitting_out(&self, _sitting_out: bool) {}
 }
 
-#[derive(Clone)]
+// The response for a player who isn't acting: check if possible, otherwise fold
+pub fn check_or_fold(call_amount: Chips, bet_this_round: Chips) -> BetResp {
+    if call_amount <= bet_this_round {
+        BetResp::Bet(call_amount)
+    } else {
+        BetResp::Fold
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+#[serde(deny_unknown_fields)]
+pub enum ReplaceRule {
+    Fixed(usize),
+    // Players holding a card of `rank` may replace `with_rank` cards instead of `max`
+    HoldingRank {
+        rank: Rank,
+        max: usize,
+        with_rank: usize,
+    },
+}
+
+impl ReplaceRule {
+    pub fn max_replace(&self, player: &PlayerState) -> usize {
+        match self {
+            ReplaceRule::Fixed(max) => *max,
+            ReplaceRule::HoldingRank{rank, max, with_rank} => {
+                if player.hand.iter().any(|c| c.card.rank == *rank) {
+                    *with_rank
+                } else {
+                    *max
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+#[serde(deny_unknown_fields)]
 pub enum Round {
     Ante,
     DrawToHand {
@@ -91,19 +163,100 @@ pub enum Round {
         quant: usize
     },
     Bet {
-        starting_player: PlayerRole,
+        #[serde(default)]
+        order: ActionOrder,
+        // The weakest hand that can open the betting. If nobody has it, the hand is passed out
+        #[serde(default)]
+        openers: Option<Kind>,
     },
     Replace {
-        max_replace_fun: fn (&PlayerState) -> usize,
+        rule: ReplaceRule,
         max_possible_replace: usize,
         extra_replace: usize,
+        #[serde(default)]
+        order: ActionOrder,
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+#[serde(deny_unknown_fields)]
+pub enum ActionOrder {
+    // The first live player left of the button acts first.
+    // Blinds override this for the first betting round
+    Position,
+    // The worst up card acts first, and posts the bring-in if the table has one.
+    // Ties go by suit_order, lowest suit first
+    BringIn {
+        suit_order: Vec<Suit>,
+    },
+    // The best hand showing acts first, ties go to the first player left of the button
+    BestVisibleHand,
+}
+
+impl Default for ActionOrder {
+    fn default() -> ActionOrder {
+        ActionOrder::Position
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+#[serde(deny_unknown_fields)]
+pub enum SplitPot {
+    HighOnly,
+    // Half of each pot goes to the best ace-to-five low.
+    // With a qualifier, the low must be unpaired with no card above that rank.
+    HighLow {
+        qualifier: Option<Rank>
+    },
+}
+
+impl Default for SplitPot {
+    fn default() -> SplitPot {
+        SplitPot::HighOnly
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+#[serde(deny_unknown_fields)]
+pub enum HandRanking {
+    High,
+    // Aces are low and straights and flushes don't count
+    AceToFive,
+    // Aces are high and straights and flushes count against you
+    DeuceToSeven,
+    // Up to four cards of different suits and ranks. Aces are low
+    Badugi,
+}
+
+impl Default for HandRanking {
+    fn default() -> HandRanking {
+        HandRanking::High
+    }
+}
+
+pub const EIGHT_OR_BETTER: Option<Rank> = Some(7);
+
+// Clubs, diamonds, hearts, spades
+pub fn bridge_suit_order() -> Vec<Suit> {
+    vec![Suit(3), Suit(2), Suit(1), Suit(0)]
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
 pub struct PokerVariant {
     pub rules: Vec<Round>,
+    // With more hole cards than this, as in Omaha, a hand is exactly this many of them and the rest from the board
     pub use_from_hand: usize,
+    #[serde(default)]
+    pub split_pot: SplitPot,
+    #[serde(default)]
+    pub ranking: HandRanking,
 }
 
 #[derive(Clone)]
@@ -123,28 +276,34 @@ pub fn texas_hold_em() -> PokerVariant {
                 facing: vec![FaceDown, FaceDown]
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::Position,
+                openers: None
             },
             DrawToCommunity {
                 quant: 3
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::Position,
+                openers: None
             },
             DrawToCommunity {
                 quant: 1
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::Position,
+                openers: None
             },
             DrawToCommunity {
                 quant: 1
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::Position,
+                openers: None
             }
         ],
         use_from_hand: 2,
+        split_pot: SplitPot::HighOnly,
+        ranking: HandRanking::High,
     }
 }
 
@@ -158,28 +317,34 @@ pub fn omaha_hold_em() -> PokerVariant {
                     facing: vec![FaceDown, FaceDown, FaceDown, FaceDown]
                 },
                 Bet {
-                    starting_player: 1
+                    order: ActionOrder::Position,
+                    openers: None
                 },
                 DrawToCommunity {
                     quant: 3
                 },
                 Bet {
-                    starting_player: 1
+                    order: ActionOrder::Position,
+                    openers: None
                 },
                 DrawToCommunity {
                     quant: 1
                 },
                 Bet {
-                    starting_player: 1
+                    order: ActionOrder::Position,
+                    openers: None
                 },
                 DrawToCommunity {
                     quant: 1
                 },
                 Bet {
-                    starting_player: 1
+                    order: ActionOrder::Position,
+                    openers: None
                 }
             ],
         use_from_hand: 2,
+        split_pot: SplitPot::HighOnly,
+        ranking: HandRanking::High,
     }
 }
 
@@ -190,13 +355,39 @@ pub fn five_card_stud() -> PokerVariant {
         rules: vec![
             Ante,
             DrawToHand{
-                facing: vec![FaceDown, FaceDown, FaceDown, FaceDown, FaceDown]
+                facing: vec![FaceDown, FaceUp]
             },
             Bet {
-                starting_player: 1
-            }
+                order: ActionOrder::BringIn {
+                    suit_order: bridge_suit_order()
+                },
+                openers: None
+            },
+            DrawToHand{
+                facing: vec![FaceUp]
+            },
+            Bet {
+                order: ActionOrder::BestVisibleHand,
+                openers: None
+            },
+            DrawToHand{
+                facing: vec![FaceUp]
+            },
+            Bet {
+                order: ActionOrder::BestVisibleHand,
+                openers: None
+            },
+            DrawToHand{
+                facing: vec![FaceUp]
+            },
+            Bet {
+                order: ActionOrder::BestVisibleHand,
+                openers: None
+            },
         ],
         use_from_hand: 5,
+        split_pot: SplitPot::HighOnly,
+        ranking: HandRanking::High,
     }
 }
 
@@ -210,42 +401,43 @@ pub fn seven_card_stud() -> PokerVariant {
                 facing: vec![FaceDown, FaceDown, FaceUp]
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::BringIn {
+                    suit_order: bridge_suit_order()
+                },
+                openers: None
             },
             DrawToHand{
                 facing: vec![FaceUp]
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::BestVisibleHand,
+                openers: None
             },
             DrawToHand {
                 facing: vec![FaceUp]
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::BestVisibleHand,
+                openers: None
             },
             DrawToHand {
                 facing: vec![FaceUp]
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::BestVisibleHand,
+                openers: None
             },
             DrawToHand {
                 facing: vec![FaceDown]
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::BestVisibleHand,
+                openers: None
             },
         ],
         use_from_hand: 5,
-    }
-}
-
-fn three_or_four_with_ace(player: &PlayerState) -> usize {
-    if player.hand.iter().any(|c| c.card.rank == 0) {
-        4
-    } else {
-        3
+        split_pot: SplitPot::HighOnly,
+        ranking: HandRanking::High,
     }
 }
 
@@ -259,39 +451,171 @@ pub fn five_card_draw() -> PokerVariant {
                 facing: vec![FaceDown; 5]
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::Position,
+                openers: None
             },
             Replace {
-                max_replace_fun: three_or_four_with_ace,
+                rule: ReplaceRule::HoldingRank {
+                    rank: 0,
+                    max: 3,
+                    with_rank: 4,
+                },
                 max_possible_replace: 3,
                 extra_replace: 4,
+                order: ActionOrder::Position,
             },
             Bet {
-                starting_player: 1
+                order: ActionOrder::Position,
+                openers: None
             }
         ],
         use_from_hand: 5,
+        split_pot: SplitPot::HighOnly,
+        ranking: HandRanking::High,
+    }
+}
+
+// Five card draw where it takes a pair of jacks or better to open
+pub fn jacks_or_better() -> PokerVariant {
+    let mut variant = five_card_draw();
+    if let Some(Round::Bet{openers, ..}) = variant.rules.iter_mut().find(|r| matches!(r, Round::Bet{..})) {
+        *openers = Some(Kind::Pair(10));
+    }
+    variant
+}
+
+pub fn omaha_hi_lo() -> PokerVariant {
+    PokerVariant {
+        split_pot: SplitPot::HighLow {
+            qualifier: EIGHT_OR_BETTER
+        },
+        ..omaha_hold_em()
+    }
+}
+
+pub fn seven_card_stud_hi_lo() -> PokerVariant {
+    PokerVariant {
+        split_pot: SplitPot::HighLow {
+            qualifier: EIGHT_OR_BETTER
+        },
+        ..seven_card_stud()
+    }
+}
+
+pub fn razz() -> PokerVariant {
+    PokerVariant {
+        ranking: HandRanking::AceToFive,
+        ..seven_card_stud()
+    }
+}
+
+pub fn badugi() -> PokerVariant {
+    use Facing::*;
+    use Round::*;
+    let mut rules = vec![
+        Ante,
+        DrawToHand{
+            facing: vec![FaceDown; 4]
+        },
+        Bet {
+            order: ActionOrder::Position,
+            openers: None
+        },
+    ];
+    for _ in 0..3 {
+        rules.push(Replace {
+            rule: ReplaceRule::Fixed(4),
+            max_possible_replace: 4,
+            extra_replace: 0,
+            order: ActionOrder::Position,
+        });
+        rules.push(Bet {
+            order: ActionOrder::Position,
+            openers: None
+        });
+    }
+    PokerVariant {
+        rules,
+        use_from_hand: 4,
+        split_pot: SplitPot::HighOnly,
+        ranking: HandRanking::Badugi,
+    }
+}
+
+pub fn deuce_to_seven_draw(draws: usize) -> PokerVariant {
+    use Facing::*;
+    use Round::*;
+    let mut rules = vec![
+        Ante,
+        DrawToHand{
+            facing: vec![FaceDown; 5]
+        },
+        Bet {
+            order: ActionOrder::Position,
+            openers: None
+        },
+    ];
+    for _ in 0..draws {
+        rules.push(Replace {
+            rule: ReplaceRule::Fixed(5),
+            max_possible_replace: 5,
+            extra_replace: 0,
+            order: ActionOrder::Position,
+        });
+        rules.push(Bet {
+            order: ActionOrder::Position,
+            openers: None
+        });
+    }
+    PokerVariant {
+        rules,
+        use_from_hand: 5,
+        split_pot: SplitPot::HighOnly,
+        ranking: HandRanking::DeuceToSeven,
     }
 }
 
 impl PokerVariantDesc {
     pub fn variant(&self) -> PokerVariant {
-        PokerVariants::table().remove(&self.name).unwrap()
+        VARIANTS.read().unwrap().get(&self.name).cloned().unwrap()
     }
 }
 
 
+fn builtin_variants() -> HashMap<String, PokerVariant> {
+    vec![
+        ("Texas Hold 'Em".to_string(), texas_hold_em()),
+        ("Omaha Hold 'Em".to_string(), omaha_hold_em()),
+        ("Seven Card Stud".to_string(), seven_card_stud()),
+        ("Five Card Stud".to_string(), five_card_stud()),
+        ("Five Card Draw".to_string(), five_card_draw()),
+        ("Jacks or Better".to_string(), jacks_or_better()),
+        ("Omaha Hi-Lo".to_string(), omaha_hi_lo()),
+        ("Seven Card Stud Hi-Lo".to_string(), seven_card_stud_hi_lo()),
+        ("Razz".to_string(), razz()),
+        ("2-7 Single Draw".to_string(), deuce_to_seven_draw(1)),
+        ("2-7 Triple Draw".to_string(), deuce_to_seven_draw(3)),
+        ("Badugi".to_string(), badugi()),
+    ].into_iter().map(|(name, v)| {
+        (name, v)
+    }).collect()
+}
+
+lazy_static! {
+    static ref VARIANTS: RwLock<HashMap<String, PokerVariant>> = RwLock::new(builtin_variants());
+}
+
 impl PokerVariants {
     pub fn table() -> HashMap<String, PokerVariant> {
-        vec![
-            ("Texas Hold 'Em".to_string(), texas_hold_em()),
-            ("Omaha Hold 'Em".to_string(), omaha_hold_em()),
-            ("Seven Card Stud".to_string(), seven_card_stud()),
-            ("Five Card Stud".to_string(), five_card_stud()),
-            ("Five Card Draw".to_string(), five_card_draw())
-        ].into_iter().map(|(name, v)| {
-            (name, v)
-        }).collect()
+        VARIANTS.read().unwrap().clone()
+    }
+
+    pub fn contains(name: &str) -> bool {
+        VARIANTS.read().unwrap().contains_key(name)
+    }
+
+    pub fn register(name: String, variant: PokerVariant) {
+        VARIANTS.write().unwrap().insert(name, variant);
     }
 
     pub fn all() -> PokerVariants {
@@ -311,19 +635,62 @@ impl DealersChoiceResp {
 }
 
 pub fn variant_max_players(variant: &PokerVariant, num_cards: usize) -> usize {
-    let mut per_player = 0;
-    let mut community = 0;
-    for round in &variant.rules {
+    (1..=num_cards).take_while(|&num_players| variant_deals_to(variant, num_cards, num_players)).last().unwrap_or(0)
+}
+
+// Deals out a whole hand with nobody folding, assuming every player replaces as many cards as they can
+fn variant_deals_to(variant: &PokerVariant, num_cards: usize, num_players: usize) -> bool {
+    let mut cards_left = num_cards;
+    // Cards outside of hands and the community. Draws after the first reshuffle the discards from this
+    let mut cards_free = num_cards;
+    // The most one player can have thrown away. Those are never dealt back to them
+    let mut own_discards = 0;
+    let mut replaced = false;
+    for (idx, round) in variant.rules.iter().enumerate() {
         use Round::*;
-        match round {
-            DrawToHand{facing} => per_player += facing.len(),
-            DrawToCommunity{quant} => community += quant,
+        let needed = match round {
+            DrawToHand{facing} => {
+                for _ in facing {
+                    if cards_left >= num_players {
+                        cards_left -= num_players;
+                        cards_free -= num_players;
+                    } else if cards_left > 0 && is_final_street(&variant.rules[idx+1..]) {
+                        cards_left -= 1;
+                        cards_free -= 1;
+                    } else {
+                        return false;
+                    }
+                }
+                0
+            },
+            DrawToCommunity{quant} => {
+                cards_free = cards_free.saturating_sub(*quant);
+                *quant
+            },
+            Replace{max_possible_replace, ..} if replaced => {
+                // Each player only needs the cards the others have thrown away
+                if *max_possible_replace > cards_free.saturating_sub(own_discards) {
+                    return false;
+                }
+                own_discards += max_possible_replace;
+                0
+            },
             Replace{max_possible_replace, extra_replace, ..} => {
-                per_player += max_possible_replace;
-                community += extra_replace;
+                replaced = true;
+                own_discards += max_possible_replace + extra_replace;
+                num_players * max_possible_replace + extra_replace
             },
-            _ => {}
+            _ => 0
+        };
+        if needed > cards_left {
+            return false;
         }
+        cards_left -= needed;
     }
-    (num_cards - community) / per_player
+    true
+}
+
+// On the final street a single community card is dealt if there aren't enough cards for everyone
+pub fn is_final_street(rounds_left: &[Round]) -> bool {
+    !rounds_left.iter().any(|r| matches!(r, Round::DrawToHand{..}))
 }
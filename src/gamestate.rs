@@ -16,6 +16,7 @@ use async_trait::async_trait;
 
 use std::collections::{HashMap, BTreeMap, HashSet};
 use std::convert::TryInto;
+use std::time::Duration;
 use serde::{Serialize, Deserialize};
 use std::sync::{Arc, Mutex};
 use core::future::Future;
@@ -34,6 +35,8 @@ pub enum RoundState {
     Bet {
         player: PlayerRole,
         last_bet: Option<(Option<PlayerRole>, Chips)>,
+        last_raise: Chips,
+        raises: usize,
         all_bets: HashMap<PlayerRole, Chips>
     },
     DrawToHand {
@@ -44,24 +47,32 @@ pub enum RoundState {
     },
     Replace {
         player: PlayerRole,
-        max_replace_fun: fn (&PlayerState) -> usize,
+        rule: ReplaceRule,
+        // The round ends when it comes back around to this player
+        first: PlayerRole,
     },
 }
 
 impl RoundState {
-    pub fn new(round: &Round) -> Self {
+    pub fn new(round: &Round, players: &PlayersState, ranking: HandRanking, rules: &SpecialRules) -> Self {
         match round {
             Round::Ante => RoundState::Ante,
-            Round::Bet{starting_player} => RoundState::Bet {
-                player: *starting_player,
+            Round::Bet{order, ..} => RoundState::Bet {
+                player: first_to_act(order, players, ranking, rules),
                 last_bet: None,
+                last_raise: 0,
+                raises: 0,
                 all_bets: HashMap::new()
             },
             Round::DrawToHand{facing} => RoundState::DrawToHand{facing: facing.clone()},
             Round::DrawToCommunity{quant} => RoundState::DrawToCommunity{quant: *quant},
-            Round::Replace{max_replace_fun, ..} => RoundState::Replace {
-                player: 1,
-                max_replace_fun: *max_replace_fun
+            Round::Replace{rule, order, ..} => {
+                let first = first_to_act(order, players, ranking, rules);
+                RoundState::Replace {
+                    player: first,
+                    rule: rule.clone(),
+                    first,
+                }
             },
         }
     }
@@ -87,6 +98,8 @@ type PlayersState = HashMap<PlayerRole, PlayerState>;
 pub struct BetState {
     pub player: PlayerRole,
     pub last_bet: Option<(Option<PlayerRole>, Chips)>,
+    pub last_raise: Chips,
+    pub raises: usize,
     pub all_bets: HashMap<PlayerRole, Chips>,
 }
 
@@ -97,6 +110,28 @@ pub struct HandState {
     pub players: HashMap<PlayerRole, PlayerState>,
     pub community_cards: CardTuple,
     pub pending_bet: Option<(BetState, Vec<PokerGlobalViewDiff<PlayerRole>>)>,
+    // Dead chips in the main pot, left over from a passed out hand
+    pub carried_pot: Chips,
+    // Replaced cards not yet shuffled back into the deck, and who threw them away
+    pub discards: Vec<(PlayerRole, Card)>,
+}
+
+// What the table settled on before the hand, on top of its ante rule
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct HandExtras {
+    // The straddling player and their bet
+    pub straddle: Option<(PlayerRole, Chips)>,
+    // Everyone antes this much instead, and the first betting round is skipped
+    pub bomb_pot: Option<Chips>,
+    pub carried_pot: Chips,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HandResult {
+    // Chips won or lost by each player
+    pub winnings: HashMap<PlayerRole, Chips>,
+    // Left in the pot for the next hand when nobody opened
+    pub carried_pot: Chips,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -194,6 +229,80 @@ impl HandStrength {
             kickers: ranks.iter().take(hand_size-1).rev().collect()
         };
     }
+
+    // The most cards of different ranks and suits wins, then the lowest. Wild cards fill in what's missing
+    fn new_badugi(cards: CardTuple, num_wild: usize) -> Self {
+        let cards: Vec<Card> = cards.iter().collect();
+        let best = (0..1usize << cards.len()).filter_map(|mask| {
+            let picked: Vec<Card> = cards.iter().enumerate().filter(|(idx, _)| mask & (1 << idx) != 0).map(|(_, &c)| c).collect();
+            let distinct = picked.iter().enumerate().all(|(idx, a)| picked[..idx].iter().all(|b| a.suit != b.suit && a.rank != b.rank));
+            if !distinct {
+                return None;
+            }
+            let mut ranks: Vec<Rank> = picked.iter().map(|c| c.rank).collect();
+            // Wild cards take the lowest ranks left, each in a suit that's left
+            let wild_ranks: Vec<Rank> = (0..NUM_RANKS).filter(|r| !ranks.contains(r)).take(std::cmp::min(num_wild, NUM_SUITS - ranks.len())).collect();
+            ranks.extend(wild_ranks);
+            ranks.sort_by(|a, b| b.cmp(a));
+            Some(BadugiHand{ranks: ranks.into_iter().collect()})
+        }).max().unwrap();
+        HandStrength {
+            kind: Kind::Badugi(best),
+            kickers: RankTuple::new(),
+        }
+    }
+
+    // Ace-to-five when aces_high is false, deuce-to-seven when it's true
+    fn new_lowball(cards: CardTuple, aces_high: bool) -> Self {
+        let mut by_rank: [usize; NUM_RANKS+1] = [0; NUM_RANKS+1];
+        for card in cards.iter() {
+            let rank = if aces_high && card.rank == 0 {
+                NUM_RANKS
+            } else {
+                card.rank % NUM_RANKS
+            };
+            by_rank[rank] += 1;
+        }
+        // Most of a kind first, then highest rank first
+        let mut grouped: Vec<(usize, Rank)> = by_rank.iter().enumerate().filter(|(_, &count)| count > 0).map(|(rank, &count)| (count, rank)).collect();
+        grouped.sort_by(|a, b| b.cmp(a));
+        let counts: Vec<usize> = grouped.iter().map(|(count, _)| *count).collect();
+        use LowKind::*;
+        let kind = match counts.as_slice() {
+            [4, ..] => FourKind,
+            [3, 2, ..] => FullHouse,
+            [3, ..] => ThreeKind,
+            [2, 2, ..] => TwoPair,
+            [2, ..] => Pair,
+            _ if aces_high && cards.len() == 5 => {
+                let is_straight = grouped[0].1 - grouped[4].1 == 4;
+                let is_flush = cards.iter().all(|c| c.suit == cards.get(0).suit);
+                match (is_straight, is_flush) {
+                    (true, true) => StraightFlush,
+                    (false, true) => Flush,
+                    (true, false) => Straight,
+                    (false, false) => NoPair,
+                }
+            },
+            _ => NoPair,
+        };
+        HandStrength {
+            kind: Kind::Low(LowHand {
+                kind,
+                ranks: grouped.into_iter().map(|(_, rank)| rank).collect(),
+            }),
+            kickers: RankTuple::new(),
+        }
+    }
+
+    // Whether this is an unpaired five card low with no card above qualifier
+    pub fn low_qualifies(&self, qualifier: Option<Rank>) -> bool {
+        match self.kind {
+            Kind::WinsItAll => true,
+            Kind::Low(LowHand{kind, ranks}) => qualifier.map(|q| kind == LowKind::NoPair && ranks.len() == 5 && ranks.get(0) <= q).unwrap_or(true),
+            _ => false,
+        }
+    }
 }
 
 impl std::fmt::Display for HandStrength {
@@ -216,6 +325,50 @@ fn explode_aces(cards: CardTuple) -> CardTuple {
 }
 
 pub fn best_hand(hand: CardTuple, community: CardTuple, hand_size: usize, rules: &SpecialRules) -> HandStrength {
+    best_hand_by(hand, community, hand_size, rules, |cards| {
+        wild_combinations(cards.0, cards.1).into_iter().filter_map(|wild_hand| {
+            aces_combos(wild_hand).into_iter().filter_map(|v|{
+                Some(HandStrength::new(v, hand_size))
+            }).max()
+        }).max().unwrap()
+    })
+}
+
+// Best ace-to-five low, used for the low half of a split pot
+pub fn best_low_hand(hand: CardTuple, community: CardTuple, hand_size: usize, rules: &SpecialRules) -> HandStrength {
+    best_lowball_hand(hand, community, hand_size, rules, false)
+}
+
+pub fn best_hand_ranked(ranking: HandRanking, hand: CardTuple, community: CardTuple, hand_size: usize, rules: &SpecialRules) -> HandStrength {
+    match ranking {
+        HandRanking::High => best_hand(hand, community, hand_size, rules),
+        HandRanking::AceToFive => best_lowball_hand(hand, community, hand_size, rules, false),
+        HandRanking::DeuceToSeven => best_lowball_hand(hand, community, hand_size, rules, true),
+        HandRanking::Badugi => best_hand_by(hand, community, hand_size, rules, |(unwild, num_wild)| HandStrength::new_badugi(unwild, num_wild)),
+    }
+}
+
+fn best_lowball_hand(hand: CardTuple, community: CardTuple, hand_size: usize, rules: &SpecialRules, aces_high: bool) -> HandStrength {
+    best_hand_by(hand, community, hand_size, rules, |(unwild, num_wild)| {
+        if num_wild == 0 {
+            return HandStrength::new_lowball(unwild, aces_high);
+        }
+        // Wild cards are best as whichever ranks are still missing, so try all of them.
+        // Use a suit that can't make a flush.
+        let suit = unwild.iter().next().map(|c| Suit((c.suit.0 + 1) % NUM_SUITS)).unwrap_or(Suit(0));
+        let wilds: CardSet = (0..NUM_RANKS).map(|rank| Card{rank, suit}).collect();
+        combinations_with_replacement(wilds.iter(), num_wild).map(|comb| {
+            let mut cards = unwild;
+            for card in comb {
+                cards.push(card);
+            }
+            HandStrength::new_lowball(cards, aces_high)
+        }).max().unwrap()
+    })
+}
+
+// Calls evaluate on each combination of hand_size cards, split into the cards that aren't wild and the number that are
+fn best_hand_by<F: Fn((CardTuple, usize)) -> HandStrength>(hand: CardTuple, community: CardTuple, hand_size: usize, rules: &SpecialRules, evaluate: F) -> HandStrength {
     for rule in rules {
         if rule.wtype == SpecialCardType::WinsItAll {
             if let Some(_) = hand.iter().find(|cs| *cs == rule.card) {
@@ -246,11 +399,7 @@ pub fn best_hand(hand: CardTuple, community: CardTuple, hand_size: usize, rules:
             unwild.push(card);
         }
 
-        wild_combinations(unwild, num_wild).into_iter().filter_map(|wild_hand| {
-            aces_combos(wild_hand).into_iter().filter_map(|v|{
-                Some(HandStrength::new(v, hand_size))
-            }).max()
-        }).max().unwrap()
+        evaluate((unwild, num_wild))
     }).max().unwrap()
 }
 
@@ -294,10 +443,53 @@ fn calc_subpots(state: &HandState) -> Vec<Subpot<PlayerRole>> {
             }
         }
     }
+    if let Some(main_pot) = retval.first_mut() {
+        main_pot.chips += state.carried_pot;
+    }
     retval
 }
 
+// Openers are judged on the best five cards, whatever the variant plays
+fn has_openers(hand: &[CardState], community: CardTuple, openers: Kind, rules: &SpecialRules) -> bool {
+    best_hand(hand.iter().map(|cs| cs.card).collect(), community, 5, rules).kind >= openers
+}
+
+// How much more the biggest bettor put in than anyone else. Nobody called it, so it goes back to them
+fn uncalled_bet(state: &HandState) -> Chips {
+    let mut bets: Vec<Chips> = state.players.values().map(|p| p.total_bet).collect();
+    bets.sort_unstable_by(|a, b| b.cmp(a));
+    bets.first().copied().unwrap_or(0) - bets.get(1).copied().unwrap_or(0)
+}
+
+// Odd chips go to the earlier runs
+fn run_share(chips: Chips, run: usize, runs: usize) -> Chips {
+    let runs = runs as Chips;
+    chips / runs + if (run as Chips) < chips % runs {1} else {0}
+}
+
+// Players still in the hand with chips behind what they've bet this round
+fn players_can_bet<'a>(players: &'a PlayersState, all_bets: &'a HashMap<PlayerRole, Chips>) -> impl Iterator<Item=PlayerRole> + 'a {
+    players.iter().filter(move |(role, p)| !p.folded && p.chips - p.total_bet > *all_bets.get(role).unwrap_or(&0)).map(|(role, _)| *role)
+}
+
+// Nobody can bet any more, so the rest of the hand is just dealt out. Draws still need decisions
+fn betting_over(state: &HandState) -> bool {
+    let in_hand = state.players.values().filter(|p| !p.folded).count();
+    in_hand > 1 && players_can_bet(&state.players, &HashMap::new()).count() <= 1
+        && !state.rounds.iter().any(|r| matches!(r, Round::Replace{..}))
+}
+
+// Only community cards are left to deal
+fn is_runout(state: &HandState) -> bool {
+    betting_over(state)
+        && state.rounds.iter().any(|r| matches!(r, Round::DrawToCommunity{..}))
+        && state.rounds.iter().all(|r| matches!(r, Round::DrawToCommunity{..} | Round::Bet{..}))
+}
+
 fn split_pot<P: Clone>(players: &[P], pot: Chips) -> Vec<(P, Chips)> {
+    if players.is_empty() {
+        return Vec::new();
+    }
     let num_players = players.len() as Chips;
     let even = pot / num_players;
     let mut left = pot % num_players;
@@ -313,25 +505,89 @@ fn split_pot<P: Clone>(players: &[P], pot: Chips) -> Vec<(P, Chips)> {
     retval
 }
 
+// low is empty when the high hand scoops the whole subpot
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct PotWinners<P> {
+    pub subpot: Subpot<P>,
+    pub high: Vec<P>,
+    pub low: Vec<P>,
+    // Taken out of the subpot before it is split
+    #[serde(default)]
+    pub rake: Chips,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[derive(TS)]
 pub struct Winners<P> {
-    pub winners_by_pot: Vec<(Subpot<P>, Vec<P>)>
+    pub winners_by_pot: Vec<PotWinners<P>>
+}
+
+impl<P: Clone> PotWinners<P> {
+    pub fn scoop(subpot: Subpot<P>, winners: Vec<P>) -> PotWinners<P> {
+        PotWinners {
+            subpot,
+            high: winners,
+            low: Vec::new(),
+            rake: 0,
+        }
+    }
+}
+
+impl<P> PotWinners<P> {
+    // Chips for the high and low halves. The odd chip goes high.
+    pub fn halves(&self) -> (Chips, Chips) {
+        let chips = self.subpot.chips - self.rake;
+        if self.low.is_empty() {
+            (chips, 0)
+        } else {
+            let low = chips / 2;
+            (chips - low, low)
+        }
+    }
 }
 
 impl<P: Clone + Eq + Hash> Winners<P> {
     pub fn totals(&self) -> HashMap<P, Chips> {
         let mut retval = HashMap::new();
-        for (Subpot{chips: pot, ..}, winners) in &self.winners_by_pot {
-            for (player, cut) in split_pot(&winners, *pot) {
+        for pot in &self.winners_by_pot {
+            let (high, low) = pot.halves();
+            for (player, cut) in split_pot(&pot.high, high).into_iter().chain(split_pot(&pot.low, low)) {
                 *retval.entry(player).or_insert(0) += cut;
             }
         }
         retval
     }
 
+    // `uncalled` is the part of the last subpot that went back to the player who bet it, which isn't raked
+    pub fn take_rake(&mut self, rake: &Rake, uncalled: Chips) {
+        let num_pots = self.winners_by_pot.len();
+        for (idx, pot) in self.winners_by_pot.iter_mut().enumerate() {
+            let returned = if idx + 1 == num_pots {uncalled} else {0};
+            pot.rake = rake.amount(pot.subpot.chips - returned);
+        }
+    }
+
+    pub fn rake(&self) -> Chips {
+        self.winners_by_pot.iter().map(|pot| pot.rake).sum()
+    }
+
+    // Keeps only the part of each pot played for on board `run` of `runs`
+    pub fn share_of_run(&mut self, run: usize, runs: usize) {
+        for pot in &mut self.winners_by_pot {
+            pot.subpot.chips = run_share(pot.subpot.chips, run, runs);
+            pot.rake = run_share(pot.rake, run, runs);
+        }
+    }
+
     pub fn convert<T: Clone>(&self, mapping: &HashMap<P, T>) -> Winners<T> {
-        let winners_by_pot = self.winners_by_pot.iter().map(|(p, l)| (p.convert(mapping), l.iter().map(|p| mapping.get(p).cloned().unwrap()).collect())).collect();
+        let convert_players = |l: &Vec<P>| l.iter().map(|p| mapping.get(p).cloned().unwrap()).collect();
+        let winners_by_pot = self.winners_by_pot.iter().map(|pot| PotWinners {
+            subpot: pot.subpot.convert(mapping),
+            high: convert_players(&pot.high),
+            low: convert_players(&pot.low),
+            rake: pot.rake,
+        }).collect();
         Winners {
             winners_by_pot
         }
@@ -347,52 +603,184 @@ impl<P: Clone + Eq + Hash> Subpot<P> {
     }
 }
 
-pub fn best_hand_use_from_hand(use_from_hand: usize, hand: CardTuple, community: CardTuple, hand_size: usize, rules: &SpecialRules) -> HandStrength {
+// The hole cards and board cards to make each possible hand from. Holding more cards than
+// use_from_hand, as in Omaha, a hand is exactly that many hole cards and the rest from the board
+fn hand_choices(use_from_hand: usize, hand: CardTuple, community: CardTuple, hand_size: usize) -> Vec<(CardTuple, CardTuple)> {
     let use_from_hand = std::cmp::min(hand.len(), use_from_hand);
-    combinations(hand.iter(), use_from_hand).into_iter().map(|combo| {
-        best_hand(combo.into_iter().collect(), community, hand_size, rules)
+    let boards: Vec<CardTuple> = if use_from_hand < hand.len() && use_from_hand < hand_size {
+        let from_board = std::cmp::min(hand_size - use_from_hand, community.len());
+        combinations(community.iter(), from_board).map(|combo| combo.into_iter().collect()).collect()
+    } else {
+        vec![community]
+    };
+    combinations(hand.iter(), use_from_hand).flat_map(|combo| {
+        let hole: CardTuple = combo.into_iter().collect();
+        boards.iter().map(move |&board| (hole, board))
+    }).collect()
+}
+
+pub fn best_hand_use_from_hand(ranking: HandRanking, use_from_hand: usize, hand: CardTuple, community: CardTuple, hand_size: usize, rules: &SpecialRules) -> HandStrength {
+    hand_choices(use_from_hand, hand, community, hand_size).into_iter().map(|(hole, board)| {
+        best_hand_ranked(ranking, hole, board, hand_size, rules)
     }).max().unwrap()
 }
 
+// The best hand by the variant's ranking, and the best qualifying low if the variant splits the pot
+pub fn player_best_hands(variant: &PokerVariant, hand: &[CardState], community: CardTuple, rules: &SpecialRules) -> (HandStrength, Option<HandStrength>) {
+    let choices = hand_choices(variant.use_from_hand, hand.iter().map(|cs| cs.card).collect(), community, 5);
+    let high = choices.iter().map(|&(hole, board)| {
+        best_hand_ranked(variant.ranking, hole, board, 5, rules)
+    }).max().unwrap();
+    let low = match variant.split_pot {
+        SplitPot::HighOnly => None,
+        SplitPot::HighLow{qualifier} => {
+            let low = choices.iter().map(|&(hole, board)| {
+                best_low_hand(hole, board, 5, rules)
+            }).max().unwrap();
+            Some(low).filter(|low| low.low_qualifies(qualifier))
+        }
+    };
+    (high, low)
+}
+
+fn best_players<'a, I: Iterator<Item=&'a PlayerRole>>(players: I, hands: &HashMap<PlayerRole, HandStrength>) -> Vec<PlayerRole> {
+    let mut winners = Vec::new();
+    let mut best = None;
+    for player in players {
+        if let Some(hand) = hands.get(player) {
+            if Some(hand) > best {
+                winners = vec![*player];
+                best = Some(hand);
+            } else if Some(hand) == best {
+                winners.push(*player);
+            }
+        }
+    }
+    winners
+}
+
 fn calc_winners(variant: &PokerVariant, state: &HandState, rules: &SpecialRules) -> Winners<PlayerRole> {
     // calculate best hands for each player
-    let best_hands: HashMap<PlayerRole, HandStrength> = state.players.iter().filter_map(|(&role, player)| {
+    let mut best_hands = HashMap::new();
+    let mut best_lows = HashMap::new();
+    for (&role, player) in &state.players {
         if player.folded {
-            return None;
+            continue;
         }
-
-        Some((role, {
-            combinations(&player.hand, variant.use_from_hand).into_iter().map(|combo| {
-                let community = state.community_cards.clone();
-                best_hand(combo.iter().map(|cs| cs.card).collect(), community, 5, rules)
-            }).max().unwrap()
-        }))
-
-    }).collect();
+        let (high, low) = player_best_hands(variant, &player.hand, state.community_cards, rules);
+        best_hands.insert(role, high);
+        if let Some(low) = low {
+            best_lows.insert(role, low);
+        }
+    }
     // figure out subpots
     let subpots = calc_subpots(state);
     // foreach subpot, split amongst top hands for players in the subpot
     let mut retval = Vec::new();
-    for Subpot{chips: pot, players} in subpots {
-        let mut piter = players.iter().copied();
-        let mut winners = vec![piter.next().unwrap()];
-        let mut best = best_hands.get(winners.first().unwrap()).unwrap();
-        for player in piter {
-            let hand = best_hands.get(&player).unwrap();
-            if hand > best {
-                winners = vec![player];
-                best = hand;
-            } else if hand == best {
-                winners.push(player);
-            }
-        }
-        retval.push((Subpot{chips: pot, players}, winners));
+    for subpot in subpots {
+        let high = best_players(subpot.players.iter(), &best_hands);
+        let low = best_players(subpot.players.iter(), &best_lows);
+        retval.push(PotWinners {
+            subpot,
+            high,
+            low,
+            rake: 0,
+        });
     }
     Winners {
         winners_by_pot: retval
     }
 }
 
+// Heads-up the button posts the small blind, otherwise the player to its left does
+pub fn blind_starting_player(num_players: usize) -> PlayerRole {
+    if num_players > 2 {
+        1
+    } else {
+        0
+    }
+}
+
+// The player who may straddle and for how much. Nobody may if that player already posts a blind
+pub fn straddle_offer(rule: StraddleRule, ante: &AnteRule, num_players: usize) -> Option<(PlayerRole, Chips)> {
+    let blinds = match ante {
+        AnteRule::Blinds(blinds) | AnteRule::BlindsAnte{blinds, ..} => blinds,
+        _ => return None,
+    };
+    let big_blind = blinds.iter().map(|b| b.amount).max()?;
+    let first_blind = blind_starting_player(num_players);
+    let role = match rule {
+        StraddleRule::UnderTheGun => (first_blind + blinds.len()) % num_players,
+        StraddleRule::Button => 0,
+    };
+    if (0..blinds.len()).any(|idx| (first_blind + idx) % num_players == role) {
+        None
+    } else {
+        Some((role, big_blind * 2))
+    }
+}
+
+// Folded and all-in players are skipped by the betting round itself
+pub fn first_to_act(order: &ActionOrder, players: &PlayersState, ranking: HandRanking, rules: &SpecialRules) -> PlayerRole {
+    let num_players = players.len();
+    let left_of_button = next_player(0, num_players);
+    // Live players showing cards, in order from the left of the button
+    let mut showing = Vec::new();
+    let mut role = left_of_button;
+    for _ in 0..num_players {
+        let player = players.get(&role).unwrap();
+        let up_cards: Vec<Card> = player.hand.iter().filter(|cs| cs.facing == Facing::FaceUp).map(|cs| cs.card).collect();
+        if !player.folded && !up_cards.is_empty() {
+            let strength = best_hand_ranked(ranking, up_cards.iter().copied().collect(), CardTuple::new(), 5, rules);
+            showing.push((role, strength, *up_cards.last().unwrap()));
+        }
+        role = next_player(role, num_players);
+    }
+    match order {
+        ActionOrder::Position => left_of_button,
+        ActionOrder::BringIn{suit_order} => {
+            // Suits break ties toward the worse card, which is the higher suit in lowball
+            let suit_key = |card: &Card| {
+                let idx = suit_order.iter().position(|s| *s == card.suit).unwrap_or(0);
+                if ranking == HandRanking::High {
+                    idx
+                } else {
+                    suit_order.len() - idx
+                }
+            };
+            showing.iter().min_by_key(|(_, strength, card)| (*strength, suit_key(card))).map(|(role, ..)| *role).unwrap_or(left_of_button)
+        },
+        ActionOrder::BestVisibleHand => {
+            let mut best: Option<(PlayerRole, HandStrength)> = None;
+            for (role, strength, _) in showing {
+                if best.map(|(_, best_strength)| strength > best_strength).unwrap_or(true) {
+                    best = Some((role, strength));
+                }
+            }
+            best.map(|(role, _)| role).unwrap_or(left_of_button)
+        },
+    }
+}
+
+// The first player to act is forced to open for the bring-in, and the round ends with them unless someone raises
+fn collect_bring_in(role: PlayerRole, bring_in: Chips, players: &PlayersState) -> (BetState, Vec<PokerGlobalViewDiff<PlayerRole>>) {
+    let player = players.get(&role).unwrap();
+    let to_collect = std::cmp::min(player.chips - player.total_bet, bring_in);
+    let bet = BetState {
+        player: next_player(role, players.len()),
+        last_bet: Some((Some(role), bring_in)),
+        last_raise: 0,
+        raises: 0,
+        all_bets: vec![(role, to_collect)].into_iter().collect(),
+    };
+    let viewdiffs = vec![PokerGlobalViewDiff::Common(PokerViewDiff::Bet {
+        bet_kind: BetDiffKind::BringIn,
+        player: role,
+        chips: to_collect,
+    })];
+    (bet, viewdiffs)
+}
+
 pub fn next_player(current_role: PlayerRole, num_players: usize) -> PlayerRole {
     let mut new_player = current_role+1;
     if new_player >= num_players {
@@ -405,43 +793,79 @@ fn collect_ante_from_players(rule: &AnteRule, players: &mut HashMap<PlayerRole,
     use AnteRule::*;
     let mut viewdiffs: Vec<PokerGlobalViewDiff<PlayerRole>> = Vec::new();
     let bet = match rule {
-        Ante(ante) => {
-            for (role, mut player) in players.iter_mut() {
-                let chips = player.chips;
-                let to_collect = std::cmp::min(chips, *ante);
-                player.total_bet += to_collect;
-                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::from_blind_name(to_collect, *role, "ante".to_string())));
-            }
+        Ante(ante) | BringIn{ante, ..} => {
+            collect_antes(*ante, players, &mut viewdiffs);
             None
         }
-        Blinds(blinds) => {
-            let mut blind_starting_player = 0;
-            if players.values().filter(|p| p.chips > 0).count() > 2 {
-                blind_starting_player = 1;
-            }
-            let mut blind_role = blind_starting_player;
-            let mut last = None;
-            let mut all_bets = HashMap::new();
-            for &Blind{amount} in blinds {
-                let player = players.get_mut(&blind_role).unwrap();
-                let chips = player.chips;
-                let to_collect = std::cmp::min(chips, amount);
-                //player.total_bet += to_collect;
-                last = Some((blind_role, amount));
-                all_bets.insert(blind_role, to_collect);
-                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::from_blind_name(to_collect, blind_role, "blind".to_string())));
-                blind_role = next_player(blind_role, players.len());
+        Blinds(blinds) => collect_blinds(blinds, players, &mut viewdiffs),
+        BlindsAnte{blinds, ante, big_blind_ante: false} => {
+            collect_antes(*ante, players, &mut viewdiffs);
+            collect_blinds(blinds, players, &mut viewdiffs)
+        }
+        BlindsAnte{blinds, ante, big_blind_ante: true} => {
+            // The big blind comes first if they can't cover both
+            let bet = collect_blinds(blinds, players, &mut viewdiffs);
+            let max_blind = blinds.iter().map(|b| b.amount).max().unwrap_or(0);
+            let big_blind_role = blinds.iter().enumerate().filter(|(_, b)| b.amount == max_blind).last()
+                .map(|(idx, _)| (blind_starting_player(players.len()) + idx) % players.len());
+            if let Some(role) = big_blind_role {
+                let blind = bet.as_ref().and_then(|b| b.all_bets.get(&role).copied()).unwrap_or(0);
+                let player = players.get_mut(&role).unwrap();
+                let to_collect = std::cmp::min(player.chips - player.total_bet - blind, *ante);
+                player.total_bet += to_collect;
+                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::from_blind_name(to_collect, role, "big blind ante".to_string())));
             }
-            last.map(|(last_blind_role, amount)| BetState {
-                player: blind_role,
-                last_bet: Some((None, amount)),
-                all_bets
-            })
+            bet
         }
     };
     (bet, viewdiffs)
 }
 
+// Antes go straight into the pot, so they don't count towards calling
+fn collect_antes(ante: Chips, players: &mut HashMap<PlayerRole, PlayerState>, viewdiffs: &mut Vec<PokerGlobalViewDiff<PlayerRole>>) {
+    for (role, player) in players.iter_mut() {
+        let to_collect = std::cmp::min(player.chips - player.total_bet, ante);
+        player.total_bet += to_collect;
+        viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::from_blind_name(to_collect, *role, "ante".to_string())));
+    }
+}
+
+fn collect_blinds(blinds: &[Blind], players: &mut HashMap<PlayerRole, PlayerState>, viewdiffs: &mut Vec<PokerGlobalViewDiff<PlayerRole>>) -> Option<BetState> {
+    let max_blind = blinds.iter().map(|b| b.amount).max().unwrap_or(0);
+    let mut blind_role = blind_starting_player(players.len());
+    let mut last = None;
+    let mut all_bets = HashMap::new();
+    for &Blind{amount} in blinds {
+        let player = players.get_mut(&blind_role).unwrap();
+        let to_collect = std::cmp::min(player.chips - player.total_bet, amount);
+        last = Some((blind_role, amount));
+        all_bets.insert(blind_role, to_collect);
+        let name = if amount == max_blind {"big blind"} else {"small blind"};
+        viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::from_blind_name(to_collect, blind_role, name.to_string())));
+        blind_role = next_player(blind_role, players.len());
+    }
+    last.map(|(_, amount)| BetState {
+        player: blind_role,
+        last_bet: Some((None, amount)),
+        last_raise: amount,
+        raises: 1,
+        all_bets
+    })
+}
+
+// The straddle counts as a raise, so the straddler gets the option and any reraise is at least as big.
+// Action starts left of the straddler, which for a button straddle is the small blind
+fn collect_straddle(bet: &mut BetState, role: PlayerRole, amount: Chips, players: &PlayersState) -> PokerGlobalViewDiff<PlayerRole> {
+    let player = players.get(&role).unwrap();
+    let to_collect = std::cmp::min(player.chips - player.total_bet, amount);
+    bet.all_bets.insert(role, to_collect);
+    bet.last_bet = Some((None, amount));
+    bet.last_raise = amount;
+    bet.raises += 1;
+    bet.player = next_player(role, players.len());
+    PokerGlobalViewDiff::Common(PokerViewDiff::from_blind_name(to_collect, role, "straddle".to_string()))
+}
+
 fn collect_bets(players: &mut PlayersState, bets: &HashMap<PlayerRole, Chips>) {
     for (role, &bet) in bets {
         players.get_mut(role).unwrap().total_bet += bet;
@@ -479,37 +903,45 @@ pub fn show_cards(variant: &PokerVariant,
     if players.values().filter(|p| !p.folded).count() < 2 {
         return;
     }
-    // last best and then to the left
-    let starting = last_bet.unwrap_or(0); // todo who actually goes first?
-    let num_players = players.len();
-    let mut role = starting;
-    loop {
+    for role in showdown_order(last_bet, players.len()) {
         let player = players.get_mut(&role).unwrap();
         if !player.folded {
-            let mut shown = Vec::new();
-            for (idx, cs) in player.hand.iter_mut().enumerate() {
-                if Facing::FaceDown == cs.facing {
-                    cs.facing = Facing::FaceUp;
-                    shown.push((idx, CardViewState::Visible(cs.clone())));
-                }
-            }
-            if !shown.is_empty() {
-                let strength = combinations(&player.hand, variant.use_from_hand).into_iter().map(|combo| {
-                    best_hand(combo.iter().map(|cs| cs.card).collect(), community_cards.clone(), 5, rules)
-                }).max().unwrap();
-                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::ShowCards {
-                    player: role,
-                    shown,
-                    strength
-                }));
-            }
+            reveal(variant, role, player, viewdiffs, community_cards, rules);
         }
-        // end
-        role = next_player(role, num_players);
-        if role == starting {
-            break;
+    }
+}
+
+// The last aggressor shows first, or the first player left of the button if everyone checked.
+// Then everyone else to the left
+fn showdown_order(last_bet: Option<PlayerRole>, num_players: usize) -> Vec<PlayerRole> {
+    let starting = last_bet.unwrap_or_else(|| next_player(0, num_players));
+    (0..num_players).map(|idx| (starting + idx) % num_players).collect()
+}
+
+// Turns up the rest of the player's cards
+fn reveal(variant: &PokerVariant,
+    role: PlayerRole,
+    player: &mut PlayerState,
+    viewdiffs: &mut Vec<PokerGlobalViewDiff<PlayerRole>>,
+    community_cards: CardTuple,
+    rules: &SpecialRules,
+    ) {
+    let mut shown = Vec::new();
+    for (idx, cs) in player.hand.iter_mut().enumerate() {
+        if Facing::FaceDown == cs.facing {
+            cs.facing = Facing::FaceUp;
+            shown.push((idx, CardViewState::Visible(cs.clone())));
         }
     }
+    if !shown.is_empty() {
+        let (strength, low) = player_best_hands(variant, &player.hand, community_cards, rules);
+        viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::ShowCards {
+            player: role,
+            shown,
+            strength,
+            low
+        }));
+    }
 }
 
 pub async fn play_poker<'a>(variant: PokerVariant,
@@ -517,10 +949,11 @@ pub async fn play_poker<'a>(variant: PokerVariant,
     players: HashMap<PlayerRole, LivePlayer>,
     spectator_channel: Option<fold_channel::Sender<Vec<PokerGlobalViewDiff<PlayerId>>, Vec<PokerGlobalViewDiff<PlayerId>>>>,
     table_rules: TableRules,
+    extras: HandExtras,
     rules: SpecialRules,
     round: usize
     ) ->
-    Result<HashMap<PlayerRole, Chips>, PokerRoundError> {
+    Result<HandResult, PokerRoundError> {
     let mut state = HandState {
         deck: starting_deck,
         rounds: variant.rules.iter().cloned().rev().collect(),
@@ -528,11 +961,24 @@ pub async fn play_poker<'a>(variant: PokerVariant,
         players: players.iter().map(|(&e, p)| (e, PlayerState{chips: p.chips, hand: Vec::new(), folded: false, total_bet: 0})).collect(),
         community_cards: CardTuple::new(),
         pending_bet: None,
+        carried_pot: extras.carried_pot,
+        discards: Vec::new(),
     };
 
     let num_players = players.len();
     let mut viewdiffs: Vec<PokerGlobalViewDiff<PlayerRole>> = Vec::new();
     let mut hand_last_bet: Option<PlayerRole> = None;
+    let mut skip_bet = extras.bomb_pot.is_some();
+    let mut asked_run_it = false;
+    // Hands are shown as soon as betting is over
+    let mut all_in = false;
+    let all_in_pause = Duration::from_millis(table_rules.all_in_pause_ms);
+    let mut runs: usize = 1;
+    // Community cards left to deal to each board
+    let mut runout_cards = 0;
+    // Openers needed in the current betting round, and who opened it and with what
+    let mut round_openers: Option<Kind> = None;
+    let mut opener: Option<(PlayerRole, HandStrength)> = None;
     let ids = players.iter().map(|(role, p)| (*role, p.player_id.clone())).collect();
 
     loop {
@@ -545,13 +991,20 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                     !player.folded
                 }).count() <= 1;
                 if end_game {
-                    let (winner_role, winner) = state.players.iter().find(|(role, player)| {
+                    let winner_role = state.players.iter().find(|(role, player)| {
                         !player.folded
-                    }).unwrap();
+                    }).map(|(role, _)| *role).unwrap();
                     let subpots = calc_subpots(&state);
-                    let winners = Winners {
-                        winners_by_pot: subpots.into_iter().map(|s| (s, vec![*winner_role])).collect()
+                    let mut winners = Winners {
+                        winners_by_pot: subpots.into_iter().map(|s| PotWinners::scoop(s, vec![winner_role])).collect()
                     };
+                    if let Some(rake) = &table_rules.rake {
+                        let bet_rounds = |rounds: &[Round]| rounds.iter().filter(|r| matches!(r, Round::Bet{..})).count();
+                        let bet_rounds_played = bet_rounds(&variant.rules) - bet_rounds(&state.rounds);
+                        if !rake.no_flop_no_drop || bet_rounds_played > 1 {
+                            winners.take_rake(rake, uncalled_bet(&state));
+                        }
+                    }
                     let mut retval = winners.totals();
                     for (&role, player) in &state.players {
                         *retval.entry(role).or_insert(0) -= player.total_bet;
@@ -559,12 +1012,93 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                     viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::Winners(winners)));
                     update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
                     viewdiffs.clear();
-                    return Ok(retval);
+
+                    let player = state.players.get_mut(&winner_role).unwrap();
+                    let to_show: Vec<usize> = match players[&winner_role].input.show_uncontested(player.hand.len()).await {
+                        ShowResp::Muck => Vec::new(),
+                        ShowResp::One(idx) => vec![idx].into_iter().filter(|&idx| idx < player.hand.len()).collect(),
+                        ShowResp::All => (0..player.hand.len()).collect(),
+                    };
+                    let mut shown = Vec::new();
+                    for idx in to_show {
+                        let cs = &mut player.hand[idx];
+                        if cs.facing == Facing::FaceDown {
+                            cs.facing = Facing::FaceUp;
+                            shown.push((idx, CardViewState::Visible(cs.clone())));
+                        }
+                    }
+                    if !shown.is_empty() {
+                        // Only what's face up counts towards the hand they show
+                        let up_cards: Vec<CardState> = player.hand.iter().filter(|cs| cs.facing == Facing::FaceUp).cloned().collect();
+                        let shown_variant = PokerVariant{use_from_hand: std::cmp::min(variant.use_from_hand, up_cards.len()), ..variant.clone()};
+                        let (strength, low) = player_best_hands(&shown_variant, &up_cards, state.community_cards, &rules);
+                        viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::ShowCards {
+                            player: winner_role,
+                            shown,
+                            strength,
+                            low
+                        }));
+                        update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
+                        viewdiffs.clear();
+                    }
+                    return Ok(HandResult{winnings: retval, carried_pot: 0});
+                } else if round_openers.is_some() && opener.is_none() {
+                    // Nobody opened, so the pot carries over to the next deal
+                    let carried = calc_subpots(&state).iter().map(|s| s.chips).sum();
+                    viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::PassedOut{carried}));
+                    update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
+                    viewdiffs.clear();
+                    let winnings = state.players.iter().map(|(&role, p)| (role, -p.total_bet)).collect();
+                    return Ok(HandResult{winnings, carried_pot: carried});
+                } else if !all_in && !state.rounds.is_empty() && betting_over(&state) {
+                    all_in = true;
+                    show_cards(&variant, &mut state.players, &mut viewdiffs, hand_last_bet, state.community_cards, &rules);
+                    continue;
+                } else if !asked_run_it && table_rules.run_it.is_some() && is_runout(&state) {
+                    asked_run_it = true;
+                    let times = table_rules.run_it.unwrap();
+                    let cards: usize = state.rounds.iter().map(|r| match r {
+                        Round::DrawToCommunity{quant} => *quant,
+                        _ => 0
+                    }).sum();
+                    if state.deck.lock().unwrap().cards_left() < cards * times {
+                        continue;
+                    }
+                    // Everyone still in the hand has to agree, including a player covering the all-ins
+                    let mut agreed = true;
+                    for role in 0..num_players {
+                        if !state.players[&role].folded && !players[&role].input.run_it(times).await {
+                            agreed = false;
+                            break;
+                        }
+                    }
+                    if agreed {
+                        runs = times;
+                        runout_cards = cards;
+                        state.rounds.clear();
+                    }
+                    continue;
                 } else if let Some(next_round) = state.rounds.pop() {
-                    let mut new_round = RoundState::new(&next_round);
-                    if let RoundState::Bet{..} = new_round {
-                        if let Some((BetState{player, last_bet, all_bets}, pending_viewdiffs)) = state.pending_bet.take() {
-                            new_round = RoundState::Bet{player, last_bet, all_bets};
+                    // Bomb pots go straight to the flop
+                    if skip_bet && matches!(next_round, Round::Bet{..}) {
+                        skip_bet = false;
+                        continue;
+                    }
+                    if let Round::Bet{openers, ..} = &next_round {
+                        round_openers = *openers;
+                    }
+                    if all_in && matches!(next_round, Round::DrawToHand{..} | Round::DrawToCommunity{..}) {
+                        tokio::time::sleep(all_in_pause).await;
+                    }
+                    let mut new_round = RoundState::new(&next_round, &state.players, variant.ranking, &rules);
+                    if let RoundState::Bet{player: first_player, ..} = new_round {
+                        hand_last_bet = None;
+                        let forced_bet = state.pending_bet.take().or_else(|| match (&next_round, &table_rules.ante) {
+                            (Round::Bet{order: ActionOrder::BringIn{..}, ..}, AnteRule::BringIn{bring_in, ..}) => Some(collect_bring_in(first_player, *bring_in, &state.players)),
+                            _ => None
+                        });
+                        if let Some((BetState{player, last_bet, last_raise, raises, all_bets}, pending_viewdiffs)) = forced_bet {
+                            new_round = RoundState::Bet{player, last_bet, last_raise, raises, all_bets};
                             for viewdiff in pending_viewdiffs {
                                 viewdiffs.push(viewdiff);
                             }
@@ -574,25 +1108,76 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                     state.cur_round = Some(new_round);
                     continue;
                 } else {
-                    show_cards(&variant, &mut state.players, &mut viewdiffs, hand_last_bet, state.community_cards, &rules);
-                    update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
-                    viewdiffs.clear();
-                    let winners = calc_winners(&variant, &state, &rules);
-                    let mut retval = winners.totals();
+                    let start_community = state.community_cards;
+                    let mut retval = HashMap::new();
+                    for run in 0..runs {
+                        if runs > 1 {
+                            tokio::time::sleep(all_in_pause).await;
+                            state.community_cards = start_community;
+                            let mut drawn = Vec::new();
+                            for _ in 0..runout_cards {
+                                let card = state.deck.lock().unwrap().draw()?;
+                                state.community_cards.push(card);
+                                drawn.push(CardViewState::Visible(CardState {
+                                    card,
+                                    facing: Facing::FaceUp
+                                }));
+                            }
+                            viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::Run{run: run + 1, times: runs, drawn}));
+                        }
+                        let mut winners = calc_winners(&variant, &state, &rules);
+                        // Winners show to take the pot, and so does the last aggressor once called. Anyone else may muck
+                        let must_show: HashSet<PlayerRole> = winners.winners_by_pot.iter()
+                            .flat_map(|pot| pot.high.iter().chain(pot.low.iter()))
+                            .copied()
+                            .chain(hand_last_bet)
+                            .collect();
+                        // The opener proves their openers, even if they drew away from them
+                        if let (0, Some((player, strength))) = (run, opener) {
+                            if !state.players[&player].folded {
+                                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::Openers{player, strength}));
+                            }
+                        }
+                        for role in showdown_order(hand_last_bet, num_players) {
+                            let player = state.players.get(&role).unwrap();
+                            if player.folded || player.hand.iter().all(|cs| cs.facing == Facing::FaceUp) {
+                                continue;
+                            }
+                            if must_show.contains(&role) || players[&role].input.show_or_muck().await {
+                                reveal(&variant, role, state.players.get_mut(&role).unwrap(), &mut viewdiffs, state.community_cards, &rules);
+                            } else {
+                                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::Muck{player: role}));
+                            }
+                            // Later players decide after seeing what was shown
+                            update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
+                            viewdiffs.clear();
+                        }
+                        if let Some(rake) = &table_rules.rake {
+                            winners.take_rake(rake, uncalled_bet(&state));
+                        }
+                        winners.share_of_run(run, runs);
+                        for (role, chips) in winners.totals() {
+                            *retval.entry(role).or_insert(0) += chips;
+                        }
+                        viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::Winners(winners)));
+                        update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
+                        viewdiffs.clear();
+                    }
                     for (&role, player) in &state.players {
                         *retval.entry(role).or_insert(0) -= player.total_bet;
                     }
-                    viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::Winners(winners)));
-                    update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
-                    viewdiffs.clear();
-                    return Ok(retval);
+                    return Ok(HandResult{winnings: retval, carried_pot: 0});
                 }
             },
             Some(round_state) => {
                 use RoundState::*;
                 match round_state {
                     Ante => {
-                        let (bet, vds) = collect_ante_from_players(&table_rules.ante, &mut state.players);
+                        let ante = extras.bomb_pot.map(AnteRule::Ante).unwrap_or_else(|| table_rules.ante.clone());
+                        let (mut bet, mut vds) = collect_ante_from_players(&ante, &mut state.players);
+                        if let (Some(bet), Some((role, amount))) = (bet.as_mut(), extras.straddle) {
+                            vds.push(collect_straddle(bet, role, amount, &state.players));
+                        }
                         if let Some(bet) = bet {
                             state.pending_bet = Some((bet, vds));
                         } else {
@@ -604,6 +1189,20 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                     },
                     DrawToHand{facing} => {
                         for facing in facing {
+                            let live_players = state.players.values().filter(|p| !p.folded).count();
+                            if state.deck.lock().unwrap().cards_left() < live_players && is_final_street(&state.rounds) {
+                                let card = state.deck.lock().unwrap().draw()?;
+                                state.community_cards.push(card);
+                                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::CommunityDraw {
+                                    drawn: vec![CardViewState::Visible(CardState {
+                                        card,
+                                        facing: Facing::FaceUp
+                                    })]
+                                }));
+                                continue;
+                            }
+                            // Once the hands are shown, the rest is dealt face up too
+                            let facing = if all_in {Facing::FaceUp} else {facing};
                             for (role, player) in state.players.iter_mut() {
                                 if !player.folded {
                                     let newcard = CardState {
@@ -636,28 +1235,22 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                         }));
                         state.cur_round = None;
                     }
-                    Bet{player: bet_role, last_bet, mut all_bets} => {
-                        let (last_bet_amount, min_bet) = if let Some((last_bet_player, last_bet_amount)) = last_bet {
+                    Bet{player: bet_role, last_bet, mut last_raise, mut raises, mut all_bets} => {
+                        let last_bet_amount = if let Some((last_bet_player, last_bet_amount)) = last_bet {
                             if last_bet_player == Some(bet_role) {
                                 collect_bets(&mut state.players, &all_bets);
                                 state.cur_round = None;
                                 continue;
                             }
-                            if last_bet_amount == 0 {
-                                assert!(table_rules.min_bet > 0);
-                                (0, table_rules.min_bet)
-                            } else {
-                                (last_bet_amount, last_bet_amount * 2)
-                            }
+                            last_bet_amount
                         } else {
-                            assert!(table_rules.min_bet > 0);
-                            (0, table_rules.min_bet)
+                            0
                         };
-                        assert!(min_bet > 0);
+                        assert!(table_rules.min_bet > 0);
                         println!("last_bet: {}", last_bet_amount);
-                        if state.players.iter().filter(|(role, player)| {
-                            player.chips - player.total_bet != *all_bets.get(&bet_role).unwrap_or(&0) && !player.folded
-                        }).count() <= 1 {
+                        // The round is over once nobody has anyone left to bet against, unless a call is still owed
+                        let can_bet: Vec<PlayerRole> = players_can_bet(&state.players, &all_bets).collect();
+                        if can_bet.len() <= 1 && can_bet.iter().all(|role| *all_bets.get(role).unwrap_or(&0) >= last_bet_amount) {
                             collect_bets(&mut state.players, &all_bets);
                             state.cur_round = None;
                             continue;
@@ -666,6 +1259,23 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                         let player = state.players.get(&bet_role).cloned().unwrap();
                         let mut this_bet = None;
                         if !player.folded && player.chips > player.total_bet + *all_bets.get(&bet_role).unwrap_or(&0) {
+                            let stack = player.chips - player.total_bet;
+                            let bet_this_round = *all_bets.get(&bet_role).unwrap_or(&0);
+                            let pot_after_call = state.carried_pot + state.players.values().map(|p| p.total_bet).sum::<Chips>()
+                                + all_bets.values().sum::<Chips>()
+                                + last_bet_amount - bet_this_round;
+                            let bet_rounds_left = state.rounds.iter().filter(|r| matches!(r, Round::Bet{..})).count();
+                            let bet_rounds = variant.rules.iter().filter(|r| matches!(r, Round::Bet{..})).count();
+                            let late_street = bet_rounds - bet_rounds_left - 1 >= bet_rounds / 2;
+                            let (min_bet, max_bet) = table_rules.betting.raise_limits(table_rules.min_bet, late_street, last_bet_amount, last_raise, raises, pot_after_call);
+                            let max_bet = std::cmp::min(max_bet, stack);
+                            assert!(min_bet > 0);
+                            // Until the pot is opened, only a player with openers can bet
+                            let can_open = match (round_openers, opener) {
+                                (Some(openers), None) => has_openers(&player.hand, state.community_cards, openers, &rules),
+                                _ => true,
+                            };
+                            let max_bet = if can_open { max_bet } else { std::cmp::min(last_bet_amount, stack) };
 
                             viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::TurnStart{player: bet_role}));
                             update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
@@ -673,10 +1283,30 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                             let player = state.players.get_mut(&bet_role).unwrap();
 
                             println!("Waiting on {}", bet_role);
-                            let f = players.get(&bet_role).unwrap().input.bet(last_bet_amount, min_bet);
-                            match f.await {
+                            let f = players.get(&bet_role).unwrap().input.bet(last_bet_amount, min_bet, max_bet);
+                            let call = std::cmp::min(last_bet_amount, stack);
+                            let resp = match f.await {
+                                // Calling more than the player has puts them all in
+                                BetResp::Bet(num_chips) if num_chips == last_bet_amount => BetResp::Bet(call),
+                                BetResp::Bet(num_chips) if num_chips == call || num_chips == max_bet || (min_bet..=max_bet).contains(&num_chips) => BetResp::Bet(num_chips),
                                 BetResp::Bet(num_chips) => {
-                                    //assert!(num_chips == 0 || num_chips == last_bet_amount || num_chips >= min_bet);
+                                    // Anything else the betting structure doesn't allow is handled like running out of time
+                                    println!("{} bet {}, but can only call {} or bet {} to {}. Checking or folding instead", bet_role, num_chips, call, min_bet, max_bet);
+                                    check_or_fold(last_bet_amount, bet_this_round)
+                                },
+                                BetResp::Fold => BetResp::Fold,
+                            };
+                            match resp {
+                                BetResp::Bet(num_chips) => {
+                                    if num_chips > last_bet_amount {
+                                        last_raise = std::cmp::max(last_raise, num_chips - last_bet_amount);
+                                        raises += 1;
+                                        hand_last_bet = Some(bet_role);
+                                        if round_openers.is_some() && opener.is_none() {
+                                            let cards: CardTuple = player.hand.iter().map(|cs| cs.card).collect();
+                                            opener = Some((bet_role, best_hand(cards, state.community_cards, 5, &rules)));
+                                        }
+                                    }
                                     if last_bet.is_none() || last_bet.unwrap().0.is_none() || num_chips > last_bet_amount {
                                         this_bet = Some((Some(bet_role), num_chips));
                                     }
@@ -694,10 +1324,12 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                         state.cur_round = Some(Bet{
                             player: next_player(bet_role, num_players),
                             last_bet: this_bet.or(last_bet),
+                            last_raise,
+                            raises,
                             all_bets
                         });
                     },
-                    Replace{max_replace_fun, player} => {
+                    Replace{rule, player, first} => {
                         let mut role = player;
                         let player = state.players.get(&role).cloned().unwrap();
                         if !player.folded {
@@ -705,7 +1337,14 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                             update_players(&players, &ids, &spectator_channel, &state, &viewdiffs, &rules, &variant, round);
                             viewdiffs.clear();
 
-                            let resp = players.get(&role).unwrap().input.replace(max_replace_fun(&player)).await;
+                            let resp = players.get(&role).unwrap().input.replace(rule.max_replace(&player)).await;
+                            // A short stub is topped up with everyone else's discards, never the player's own
+                            if state.deck.lock().unwrap().cards_left() < resp.len() && state.discards.iter().any(|(by, _)| *by != role) {
+                                let (own, discards): (Vec<(PlayerRole, Card)>, Vec<(PlayerRole, Card)>) = std::mem::take(&mut state.discards).into_iter().partition(|(by, _)| *by == role);
+                                state.discards = own;
+                                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::Reshuffle{cards: discards.len()}));
+                                state.deck.lock().unwrap().reshuffle(discards.into_iter().map(|(_, card)| card).collect())?;
+                            }
                             let mut player = state.players.get_mut(&role).unwrap();
                             let mut discard = Vec::new();
                             let mut drawn = Vec::new();
@@ -715,17 +1354,22 @@ pub async fn play_poker<'a>(variant: PokerVariant,
                                 player.hand[idx].card = state.deck.lock().unwrap().draw()?;
                                 drawn.push(player.hand[idx].clone());
                             }
-                            viewdiffs.push(PokerGlobalViewDiff::Replace {
-                                player: role,
-                                discard,
-                                drawn
-                            });
+                            state.discards.extend(discard.iter().map(|cs| (role, cs.card)));
+                            if drawn.is_empty() {
+                                viewdiffs.push(PokerGlobalViewDiff::Common(PokerViewDiff::StandPat{player: role}));
+                            } else {
+                                viewdiffs.push(PokerGlobalViewDiff::Replace {
+                                    player: role,
+                                    discard,
+                                    drawn
+                                });
+                            }
                         }
                         role = next_player(role, num_players);
-                        if role == 1 {
+                        if role == first {
                             state.cur_round = None;
                         } else {
-                            state.cur_round = Some(Replace{max_replace_fun, player: role});
+                            state.cur_round = Some(Replace{rule, player: role, first});
                         }
                     }
                 }
@@ -978,6 +1622,8 @@ mod test {
             players,
             community_cards: CardTuple::new(),
             pending_bet: None,
+            carried_pot: 0,
+            discards: Vec::new(),
         }
     }
 
@@ -1136,6 +1782,116 @@ mod test {
         assert!(result == expected, "{:?} != {:?}", result, expected);
     }
 
+    #[test]
+    fn test_take_rake() {
+        let rake = Rake{percent: 5, cap: Some(3), no_flop_no_drop: false};
+        let mut winners = Winners {
+            winners_by_pot: vec![
+                PotWinners::scoop(Subpot{chips: 40, players: vec![0, 1, 2]}, vec![0]),
+                PotWinners::scoop(Subpot{chips: 100, players: vec![1, 2]}, vec![2]),
+            ],
+        };
+        winners.take_rake(&rake, 0);
+        assert_eq!(winners.rake(), 5);
+        assert_eq!(winners.totals(), vec![(0, 38), (2, 97)].into_iter().collect());
+
+        // The uncalled part of the last pot goes back without being raked
+        let mut winners = Winners {
+            winners_by_pot: vec![PotWinners::scoop(Subpot{chips: 100, players: vec![1]}, vec![1])],
+        };
+        winners.take_rake(&Rake{cap: None, ..rake}, 20);
+        assert_eq!(winners.rake(), 4);
+
+        let player = |total_bet| PlayerState{chips: 100, hand: Vec::new(), folded: false, total_bet};
+        let state = make_test_calc_winners_state(vec![(0, player(17)), (1, player(8)), (2, player(12))].into_iter().collect());
+        assert_eq!(uncalled_bet(&state), 5);
+    }
+
+    #[test]
+    fn test_share_of_run() {
+        let rake = Rake{percent: 10, cap: None, no_flop_no_drop: false};
+        let winners = Winners {
+            winners_by_pot: vec![PotWinners::scoop(Subpot{chips: 101, players: vec![0, 1]}, vec![0])],
+        };
+        let mut totals = 0;
+        for run in 0..3 {
+            let mut winners = winners.clone();
+            winners.take_rake(&rake, 0);
+            winners.share_of_run(run, 3);
+            totals += winners.totals()[&0];
+        }
+        // The rake is taken once from the whole pot, not once per board
+        assert_eq!(totals, 91);
+        assert_eq!((0..3).map(|run| run_share(101, run, 3)).collect::<Vec<Chips>>(), vec![34, 34, 33]);
+    }
+
+    #[test]
+    fn test_badugi() {
+        let badugi = |cards: Vec<(Rank, usize)>, rules: &SpecialRules| -> HandStrength {
+            best_hand_ranked(HandRanking::Badugi, cards.into_iter().map(|(rank, suit)| Card{rank, suit: Suit(suit)}).collect(), CardTuple::new(), 5, rules)
+        };
+        let wheel = badugi(vec![(0, 0), (1, 1), (2, 2), (3, 3)], &Vec::new());
+        let king_high = badugi(vec![(0, 0), (1, 1), (2, 2), (12, 3)], &Vec::new());
+        // A paired three only plays once, so the four card king beats it
+        let three_card = badugi(vec![(0, 0), (1, 1), (2, 2), (2, 3)], &Vec::new());
+        let two_suits = badugi(vec![(0, 0), (1, 0), (2, 1), (3, 1)], &Vec::new());
+        assert!(wheel > king_high);
+        assert!(king_high > three_card);
+        assert!(three_card > two_suits);
+        assert_eq!(wheel.kind.to_string(), "Badugi, Four high");
+        assert_eq!(three_card.kind.to_string(), "Three card hand, Three high");
+        assert_eq!(two_suits.kind.to_string(), "Two card hand, Three high");
+
+        let wild_king = vec![SpecialCard{wtype: SpecialCardType::Wild, card: Card{rank: 12, suit: Suit(0)}}];
+        let wild = badugi(vec![(0, 0), (1, 0), (12, 0), (4, 1)], &wild_king);
+        assert_eq!(wild.kind.to_string(), "Three card hand, Five high");
+    }
+
+    #[test]
+    fn test_has_openers() {
+        let hand = |ranks: Vec<Rank>| -> Vec<CardState> {
+            ranks.into_iter().enumerate().map(|(idx, rank)| CardState{card: Card{rank, suit: Suit(idx % NUM_SUITS)}, facing: Facing::FaceDown}).collect()
+        };
+        let jacks = Kind::Pair(10);
+        assert!(has_openers(&hand(vec![10, 10, 1, 4, 7]), CardTuple::new(), jacks, &Vec::new()));
+        assert!(!has_openers(&hand(vec![9, 9, 1, 4, 7]), CardTuple::new(), jacks, &Vec::new()));
+        // Any two pair beats a pair of jacks
+        assert!(has_openers(&hand(vec![1, 1, 2, 2, 7]), CardTuple::new(), jacks, &Vec::new()));
+        assert!(has_openers(&hand(vec![0, 0, 1, 4, 7]), CardTuple::new(), jacks, &Vec::new()));
+    }
+
+    #[test]
+    fn test_carried_pot() {
+        let player = |total_bet, folded| PlayerState{chips: 100, hand: Vec::new(), folded, total_bet};
+        let mut state = make_test_calc_winners_state(vec![(0, player(5, false)), (1, player(10, false)), (2, player(10, true))].into_iter().collect());
+        state.carried_pot = 12;
+        // Carried chips are dead money in the main pot
+        assert_eq!(calc_subpots(&state), vec![
+            Subpot{chips: 27, players: vec![0, 1]},
+            Subpot{chips: 10, players: vec![1]},
+        ]);
+    }
+
+    #[test]
+    fn test_showdown_order() {
+        assert_eq!(showdown_order(Some(2), 4), vec![2, 3, 0, 1]);
+        // Checked down, so the first player after the button goes first
+        assert_eq!(showdown_order(None, 3), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_is_runout() {
+        let player = |chips, total_bet| PlayerState{chips, hand: Vec::new(), folded: false, total_bet};
+        let mut state = make_test_calc_winners_state(vec![(0, player(50, 50)), (1, player(100, 50)), (2, player(100, 10))].into_iter().collect());
+        state.rounds = vec![Round::Bet{order: ActionOrder::Position, openers: None}, Round::DrawToCommunity{quant: 1}];
+        // Two players can still bet
+        assert!(!is_runout(&state));
+        state.players.get_mut(&2).unwrap().folded = true;
+        assert!(is_runout(&state));
+        state.rounds.push(Round::DrawToHand{facing: vec![Facing::FaceDown]});
+        assert!(!is_runout(&state));
+    }
+
     #[test]
     fn test_split_pot() {
         let players = vec![0, 2, 3];
@@ -1305,4 +2061,616 @@ mod test {
         let expected: HashMap<PlayerRole, Chips> = vec![(0, 54)].into_iter().collect();
         assert!(result == expected, "{:?} != {:?}", result, expected);
     }
+
+    #[test]
+    fn test_best_low_hand() {
+        let low = |cards: Vec<(usize, usize)>| best_low_hand(cards.into_iter().map(|c| c.into()).collect(), CardTuple::new(), 5, &vec![]);
+        let wheel = low(vec![(0, 0), (1, 1), (2, 2), (3, 3), (4, 0)]);
+        let six_four = low(vec![(0, 0), (1, 1), (2, 2), (3, 3), (5, 0)]);
+        let six_five = low(vec![(0, 0), (1, 1), (2, 2), (4, 3), (5, 0)]);
+        let nine = low(vec![(0, 0), (1, 1), (2, 2), (3, 3), (8, 0)]);
+        let pair = low(vec![(0, 0), (0, 1), (2, 2), (3, 3), (4, 0)]);
+        assert!(wheel > six_four);
+        assert!(six_four > six_five);
+        assert!(six_five > nine);
+        assert!(nine > pair);
+        assert!(six_five.low_qualifies(EIGHT_OR_BETTER));
+        assert!(!nine.low_qualifies(EIGHT_OR_BETTER));
+        assert!(nine.low_qualifies(None));
+        assert!(!pair.low_qualifies(EIGHT_OR_BETTER));
+        assert_eq!(six_five.to_string(), "Six-Five low");
+
+        // Twos are wild, so two of them should fill in the missing Two and Four
+        let wild = best_low_hand(vec![(0, 0), (1, 1), (1, 2), (2, 2), (5, 0)].into_iter().map(|c| c.into()).collect(), CardTuple::new(), 5, &SpecialCardGroup::all()[0].cards);
+        assert!(wild == six_four, "{:?}", wild);
+    }
+
+    #[test]
+    fn test_high_card_kickers() {
+        let high = |cards: Vec<(usize, usize)>| best_hand(cards.into_iter().map(|c| c.into()).collect(), CardTuple::new(), 5, &vec![]);
+        let king_five = high(vec![(12, 0), (4, 1), (1, 2), (10, 3), (8, 0)]);
+        let king_three = high(vec![(12, 0), (2, 1), (1, 2), (10, 3), (8, 0)]);
+        assert!(king_five > king_three);
+    }
+
+    #[test]
+    fn test_calc_winners_hi_lo() {
+        let hi_lo_stud = PokerVariant {
+            split_pot: SplitPot::HighLow {
+                qualifier: EIGHT_OR_BETTER
+            },
+            ..five_card_stud()
+        };
+        let mut players = HashMap::new();
+        players.insert(0, PlayerState {
+            chips: 0,
+            hand: make_cards(vec![(0, 0), (1, 1), (2, 2), (3, 3), (5, 0)]),
+            folded: false,
+            total_bet: 10
+        });
+        players.insert(1, PlayerState {
+            chips: 0,
+            hand: make_cards(vec![(12, 0), (12, 1), (8, 2), (7, 3), (6, 0)]),
+            folded: false,
+            total_bet: 10
+        });
+        let state = make_test_calc_winners_state(players.clone());
+        let winners = calc_winners(&hi_lo_stud, &state, &vec![]);
+        assert_eq!(winners.winners_by_pot[0].high, vec![1]);
+        assert_eq!(winners.winners_by_pot[0].low, vec![0]);
+        let expected: HashMap<PlayerRole, Chips> = vec![(0, 10), (1, 10)].into_iter().collect();
+        assert_eq!(winners.totals(), expected);
+
+        // Nobody qualifies for low, so the high hand scoops
+        players.get_mut(&0).unwrap().hand = make_cards(vec![(0, 0), (1, 1), (2, 2), (3, 3), (8, 0)]);
+        let state = make_test_calc_winners_state(players.clone());
+        let expected: HashMap<PlayerRole, Chips> = vec![(1, 20)].into_iter().collect();
+        assert_eq!(calc_winners(&hi_lo_stud, &state, &vec![]).totals(), expected);
+
+        // Two equal lows get quartered
+        players.get_mut(&0).unwrap().hand = make_cards(vec![(0, 0), (1, 1), (2, 2), (3, 3), (5, 0)]);
+        players.insert(2, PlayerState {
+            chips: 0,
+            hand: make_cards(vec![(0, 1), (1, 2), (2, 3), (3, 0), (5, 1)]),
+            folded: false,
+            total_bet: 10
+        });
+        let state = make_test_calc_winners_state(players);
+        let expected: HashMap<PlayerRole, Chips> = vec![(0, 8), (1, 15), (2, 7)].into_iter().collect();
+        assert_eq!(calc_winners(&hi_lo_stud, &state, &vec![]).totals(), expected);
+
+        // Omaha plays exactly two hole cards, so kings can't use the wheel on the board for low
+        let mut players = HashMap::new();
+        players.insert(0, PlayerState {
+            chips: 0,
+            hand: make_cards(vec![(12, 1), (12, 2), (11, 3), (11, 0)]),
+            folded: false,
+            total_bet: 10
+        });
+        players.insert(1, PlayerState {
+            chips: 0,
+            hand: make_cards(vec![(6, 1), (7, 2), (10, 3), (9, 1)]),
+            folded: false,
+            total_bet: 10
+        });
+        let mut state = make_test_calc_winners_state(players);
+        state.community_cards = vec![(0, 0), (1, 1), (2, 2), (3, 3), (4, 0)].into_iter().map(|c| c.into()).collect();
+        let winners = calc_winners(&omaha_hi_lo(), &state, &vec![]);
+        assert_eq!(winners.winners_by_pot[0].high, vec![0]);
+        assert_eq!(winners.winners_by_pot[0].low, vec![1]);
+        let expected: HashMap<PlayerRole, Chips> = vec![(0, 10), (1, 10)].into_iter().collect();
+        assert_eq!(winners.totals(), expected);
+    }
+
+    #[test]
+    fn test_deuce_to_seven() {
+        let low = |cards: Vec<(usize, usize)>| best_hand_ranked(HandRanking::DeuceToSeven, cards.into_iter().map(|c| c.into()).collect(), CardTuple::new(), 5, &vec![]);
+        let seven_five = low(vec![(6, 0), (4, 1), (3, 2), (2, 3), (1, 0)]);
+        let eight_six = low(vec![(7, 0), (5, 1), (3, 2), (2, 3), (1, 0)]);
+        let wheel = low(vec![(0, 0), (1, 1), (2, 2), (3, 3), (4, 0)]);
+        let straight = low(vec![(5, 0), (4, 1), (3, 2), (2, 3), (1, 0)]);
+        let flush = low(vec![(7, 0), (5, 0), (3, 0), (2, 0), (1, 0)]);
+        let pair = low(vec![(1, 0), (1, 1), (3, 2), (4, 3), (5, 0)]);
+        assert!(seven_five > eight_six);
+        // Aces are high, so A-2-3-4-5 is just an ace high
+        assert!(eight_six > wheel);
+        assert!(wheel > pair);
+        assert!(pair > straight);
+        assert!(straight > flush);
+        assert_eq!(seven_five.to_string(), "Seven-Five low");
+        assert_eq!(wheel.to_string(), "Ace-Five low");
+    }
+
+    #[test]
+    fn test_calc_winners_razz() {
+        let mut players = HashMap::new();
+        players.insert(0, PlayerState {
+            chips: 0,
+            hand: make_cards(vec![(12, 0), (12, 1), (12, 2), (8, 3), (8, 0), (3, 1), (2, 2)]),
+            folded: false,
+            total_bet: 10
+        });
+        players.insert(1, PlayerState {
+            chips: 0,
+            hand: make_cards(vec![(0, 0), (1, 1), (9, 2), (10, 3), (11, 0), (4, 1), (6, 2)]),
+            folded: false,
+            total_bet: 10
+        });
+        let state = make_test_calc_winners_state(players);
+        let expected: HashMap<PlayerRole, Chips> = vec![(1, 20)].into_iter().collect();
+        assert_eq!(calc_winners(&razz(), &state, &vec![]).totals(), expected);
+    }
+
+    #[test]
+    fn test_raise_limits() {
+        // Big blind of 2 posted, 3 in the pot, facing 2 with nothing in
+        let no_limit = BettingStructure::NoLimit;
+        assert_eq!(no_limit.raise_limits(2, false, 2, 2, 1, 5), (4, Chips::MAX));
+        // Someone raised to 10, so the next raise must be at least 8 more
+        assert_eq!(no_limit.raise_limits(2, false, 10, 8, 2, 15), (18, Chips::MAX));
+        // Nobody has bet yet
+        assert_eq!(no_limit.raise_limits(2, false, 0, 0, 0, 20), (2, Chips::MAX));
+
+        let pot_limit = BettingStructure::PotLimit;
+        assert_eq!(pot_limit.raise_limits(2, false, 2, 2, 1, 5), (4, 7));
+        assert_eq!(pot_limit.raise_limits(2, false, 0, 0, 0, 20), (2, 20));
+
+        let fixed_limit = BettingStructure::FixedLimit{small_bet: 2, big_bet: 4, raise_cap: 4};
+        assert_eq!(fixed_limit.raise_limits(2, false, 2, 2, 1, 5), (4, 4));
+        assert_eq!(fixed_limit.raise_limits(2, true, 0, 0, 0, 20), (4, 4));
+        assert_eq!(fixed_limit.raise_limits(2, true, 12, 4, 3, 40), (16, 16));
+        // Capped, so calling is the only option
+        assert_eq!(fixed_limit.raise_limits(2, true, 16, 4, 4, 40), (16, 16));
+        assert_eq!(fixed_limit.rescaled(2, 4), BettingStructure::FixedLimit{small_bet: 4, big_bet: 8, raise_cap: 4});
+    }
+
+    #[test]
+    fn test_first_to_act_stud() {
+        let card = |rank, suit, facing| CardState{card: Card{rank, suit: Suit(suit)}, facing};
+        let player = |hand: Vec<CardState>| PlayerState{chips: 100, hand, folded: false, total_bet: 1};
+        let bring_in = ActionOrder::BringIn{suit_order: bridge_suit_order()};
+
+        // Threes of diamonds and clubs tie, and clubs is the lowest suit
+        let mut players: PlayersState = vec![
+            (0, player(vec![card(8, 0, Facing::FaceDown), card(2, 2, Facing::FaceUp)])),
+            (1, player(vec![card(8, 1, Facing::FaceDown), card(2, 3, Facing::FaceUp)])),
+            (2, player(vec![card(8, 2, Facing::FaceDown), card(12, 1, Facing::FaceUp)])),
+        ].into_iter().collect();
+        assert_eq!(first_to_act(&bring_in, &players, HandRanking::High, &vec![]), 1);
+        // In razz the king is the worst card
+        assert_eq!(first_to_act(&bring_in, &players, HandRanking::AceToFive, &vec![]), 2);
+        // And the king of spades is worse than the king of hearts
+        players.get_mut(&0).unwrap().hand[1] = card(12, 0, Facing::FaceUp);
+        assert_eq!(first_to_act(&bring_in, &players, HandRanking::AceToFive, &vec![]), 0);
+        // Folded players don't bring it in
+        players.get_mut(&0).unwrap().folded = true;
+        players.get_mut(&2).unwrap().folded = true;
+        assert_eq!(first_to_act(&bring_in, &players, HandRanking::AceToFive, &vec![]), 1);
+
+        // A pair of threes showing beats ace-three and king-queen
+        let mut players: PlayersState = vec![
+            (0, player(vec![card(8, 0, Facing::FaceDown), card(2, 2, Facing::FaceUp), card(2, 1, Facing::FaceUp)])),
+            (1, player(vec![card(8, 1, Facing::FaceDown), card(2, 3, Facing::FaceUp), card(0, 0, Facing::FaceUp)])),
+            (2, player(vec![card(8, 2, Facing::FaceDown), card(12, 1, Facing::FaceUp), card(11, 1, Facing::FaceUp)])),
+        ].into_iter().collect();
+        assert_eq!(first_to_act(&ActionOrder::BestVisibleHand, &players, HandRanking::High, &vec![]), 0);
+        // Equal hands showing go to the first player left of the button
+        players.get_mut(&0).unwrap().hand[2] = card(0, 2, Facing::FaceUp);
+        players.get_mut(&1).unwrap().hand[1] = card(2, 0, Facing::FaceUp);
+        assert_eq!(first_to_act(&ActionOrder::BestVisibleHand, &players, HandRanking::High, &vec![]), 1);
+
+        let (bet, viewdiffs) = collect_bring_in(1, 2, &players);
+        assert_eq!(bet.player, 2);
+        assert_eq!(bet.last_bet, Some((Some(1), 2)));
+        assert_eq!(bet.all_bets, vec![(1, 2)].into_iter().collect());
+        assert_eq!(viewdiffs.len(), 1);
+        assert_eq!(viewdiffs[0].player_diff(None), PokerViewDiff::Bet{bet_kind: BetDiffKind::BringIn, player: 1, chips: 2});
+    }
+
+    // Bets from the script in order, then calls. Records who was asked to act and what they saw
+    struct ScriptedInput {
+        role: PlayerRole,
+        bets: Mutex<Vec<BetResp>>,
+        // Whether to show a losing hand
+        show: bool,
+        acted: Arc<Mutex<Vec<PlayerRole>>>,
+        replaced: Arc<Mutex<Vec<PlayerRole>>>,
+        // The amount to call and the most the player could bet, each time they were asked
+        offers: Mutex<Vec<(Chips, Chips)>>,
+        diffs: Mutex<Vec<PokerViewDiff<PlayerId>>>,
+    }
+
+    #[async_trait]
+    impl PlayerInputSource for ScriptedInput {
+        async fn bet(&self, call_amount: Chips, _min_bet: Chips, max_bet: Chips) -> BetResp {
+            self.acted.lock().unwrap().push(self.role);
+            self.offers.lock().unwrap().push((call_amount, max_bet));
+            let mut bets = self.bets.lock().unwrap();
+            if bets.is_empty() {
+                BetResp::Bet(call_amount)
+            } else {
+                bets.remove(0)
+            }
+        }
+
+        async fn replace(&self, _max_can_replace: usize) -> ReplaceResp {
+            self.replaced.lock().unwrap().push(self.role);
+            Vec::new()
+        }
+
+        async fn dealers_choice(&self, _variants: Vec<PokerVariantDesc>) -> DealersChoiceResp {
+            panic!("Not dealer's choice");
+        }
+
+        async fn show_or_muck(&self) -> bool {
+            self.show
+        }
+
+        async fn show_uncontested(&self, _num_cards: usize) -> ShowResp {
+            if self.show {ShowResp::All} else {ShowResp::Muck}
+        }
+
+        fn update(&self, update: PokerViewUpdate) {
+            for log in update.diff {
+                for diff in log.log {
+                    if let TableViewDiff::GameDiff(diff) = diff {
+                        self.diffs.lock().unwrap().push(diff);
+                    }
+                }
+            }
+        }
+    }
+
+    // A hand played out by ScriptedInputs, as player 0 saw it
+    struct ScriptedHand {
+        acted: Vec<PlayerRole>,
+        replaced: Vec<PlayerRole>,
+        offers: Vec<Vec<(Chips, Chips)>>,
+        diffs: Vec<PokerViewDiff<PlayerId>>,
+        result: HandResult,
+    }
+
+    // Deals from the end of `deck`. Players in `mucks` muck when they can't win
+    async fn play_scripted_hand(variant: PokerVariant, ante: AnteRule, extras: HandExtras, scripts: Vec<Vec<BetResp>>, mucks: &[PlayerRole], deck: VecDeck) -> ScriptedHand {
+        let acted = Arc::new(Mutex::new(Vec::new()));
+        let replaced = Arc::new(Mutex::new(Vec::new()));
+        let inputs: Vec<Arc<ScriptedInput>> = scripts.into_iter().enumerate().map(|(role, bets)| Arc::new(ScriptedInput {
+            role,
+            bets: Mutex::new(bets),
+            show: !mucks.contains(&role),
+            acted: acted.clone(),
+            replaced: replaced.clone(),
+            offers: Mutex::new(Vec::new()),
+            diffs: Mutex::new(Vec::new()),
+        })).collect();
+        let players = inputs.iter().map(|input| (input.role, LivePlayer {
+            player_id: input.role.to_string(),
+            chips: 100,
+            input: input.clone(),
+        })).collect();
+        let table_rules = TableRules {
+            ante,
+            ante_name: "ante".to_string(),
+            min_bet: 2,
+            betting: BettingStructure::NoLimit,
+            rake: None,
+            straddle: None,
+            bomb_pot: None,
+            run_it: None,
+            all_in_pause_ms: 0,
+        };
+        let deck: Box<dyn Deck + Send> = Box::new(deck);
+        let result = play_poker(variant, Mutex::new(deck), players, None, table_rules, extras, Vec::new(), 0).await.unwrap();
+        let acted = acted.lock().unwrap().clone();
+        let replaced = replaced.lock().unwrap().clone();
+        let offers = inputs.iter().map(|input| input.offers.lock().unwrap().clone()).collect();
+        let diffs = inputs[0].diffs.lock().unwrap().clone();
+        ScriptedHand{acted, replaced, offers, diffs, result}
+    }
+
+    // Returns the order players were asked to bet in and the order they showed down in
+    async fn play_scripted(variant: PokerVariant, ante: AnteRule, extras: HandExtras, scripts: Vec<Vec<BetResp>>) -> (Vec<PlayerRole>, Vec<PlayerId>) {
+        let hand = play_scripted_hand(variant, ante, extras, scripts, &[], standard_deck().clone()).await;
+        let shown = hand.diffs.into_iter().filter_map(|diff| match diff {
+            PokerViewDiff::ShowCards{player, ..} => Some(player),
+            _ => None,
+        }).collect();
+        (hand.acted, shown)
+    }
+
+    #[test]
+    fn test_collect_blinds_ante() {
+        let blinds = vec![Blind{amount: 1}, Blind{amount: 2}];
+        let player = || PlayerState{chips: 100, hand: Vec::new(), folded: false, total_bet: 0};
+        let mut players: PlayersState = (0..4).map(|r| (r, player())).collect();
+        let rule = AnteRule::BlindsAnte{blinds: blinds.clone(), ante: 1, big_blind_ante: false};
+        let (bet, viewdiffs) = collect_ante_from_players(&rule, &mut players);
+        assert!(players.values().all(|p| p.total_bet == 1));
+        assert_eq!(bet.unwrap().all_bets, vec![(1, 1), (2, 2)].into_iter().collect());
+        assert_eq!(viewdiffs.len(), 6);
+
+        let mut players: PlayersState = (0..4).map(|r| (r, player())).collect();
+        let rule = AnteRule::BlindsAnte{blinds, ante: 2, big_blind_ante: true};
+        let (bet, viewdiffs) = collect_ante_from_players(&rule, &mut players);
+        // Only the big blind pays the ante
+        assert_eq!((0..4).map(|r| players[&r].total_bet).collect::<Vec<Chips>>(), vec![0, 0, 2, 0]);
+        assert_eq!(bet.unwrap().all_bets, vec![(1, 1), (2, 2)].into_iter().collect());
+        assert_eq!(viewdiffs.last().unwrap().player_diff(None), PokerViewDiff::from_blind_name(2, 2, "big blind ante".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_action_order_blinds() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        let (acted, shown) = play_scripted(texas_hold_em(), blinds, HandExtras::default(), vec![Vec::new(); 4]).await;
+        // UTG first and the big blind gets the option preflop, then left of the button
+        assert_eq!(acted, vec![3, 0, 1, 2, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0]);
+        // Nobody bet the river, so the first player left of the button shows first
+        assert_eq!(shown, vec!["1", "2", "3", "0"]);
+    }
+
+    #[tokio::test]
+    async fn test_action_order_heads_up() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        let (acted, shown) = play_scripted(texas_hold_em(), blinds, HandExtras::default(), vec![Vec::new(); 2]).await;
+        // The button is the small blind and acts first preflop, then last after the flop
+        assert_eq!(acted, vec![0, 1, 1, 0, 1, 0, 1, 0]);
+        assert_eq!(shown, vec!["1", "0"]);
+    }
+
+    #[tokio::test]
+    async fn test_action_order_skips_folded() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        let (acted, shown) = play_scripted(texas_hold_em(), blinds, HandExtras::default(), vec![Vec::new(), vec![BetResp::Fold], Vec::new()]).await;
+        // The small blind folds, so the big blind is first to act after the flop
+        assert_eq!(acted, vec![0, 1, 2, 2, 0, 2, 0, 2, 0]);
+        assert_eq!(shown, vec!["2", "0"]);
+    }
+
+    #[tokio::test]
+    async fn test_showdown_last_aggressor() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        // The button bets the river and gets called, so it shows first
+        let button = vec![BetResp::Bet(2), BetResp::Bet(0), BetResp::Bet(0), BetResp::Bet(4)];
+        let (acted, shown) = play_scripted(texas_hold_em(), blinds, HandExtras::default(), vec![button, Vec::new()]).await;
+        assert_eq!(acted, vec![0, 1, 1, 0, 1, 0, 1, 0, 1]);
+        assert_eq!(shown, vec!["0", "1"]);
+
+        // With antes, the player left of the button acts first on every street
+        let (acted, _) = play_scripted(texas_hold_em(), AnteRule::Ante(1), HandExtras::default(), vec![Vec::new(); 3]).await;
+        assert_eq!(acted, vec![1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    // Who showed and who mucked, in the order they did
+    fn showdown(diffs: &[PokerViewDiff<PlayerId>]) -> Vec<(PlayerId, bool)> {
+        diffs.iter().filter_map(|diff| match diff {
+            PokerViewDiff::ShowCards{player, ..} => Some((player.clone(), true)),
+            PokerViewDiff::Muck{player} => Some((player.clone(), false)),
+            _ => None,
+        }).collect()
+    }
+
+    fn high_winners(diffs: &[PokerViewDiff<PlayerId>]) -> HashSet<PlayerId> {
+        diffs.iter().filter_map(|diff| match diff {
+            PokerViewDiff::Winners(winners) => Some(winners.winners_by_pot.iter().flat_map(|pot| pot.high.iter().cloned()).collect::<Vec<_>>()),
+            _ => None,
+        }).flatten().collect()
+    }
+
+    #[tokio::test]
+    async fn test_showdown_muck() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        // Checked down, so only the winner has to show
+        let hand = play_scripted_hand(texas_hold_em(), blinds.clone(), HandExtras::default(), vec![Vec::new(); 3], &[0, 1, 2], standard_deck().clone()).await;
+        let winners = high_winners(&hand.diffs);
+        assert_eq!(winners.len(), 1);
+        let expected: Vec<(PlayerId, bool)> = showdown_order(None, 3).into_iter()
+            .map(|role| (role.to_string(), winners.contains(&role.to_string())))
+            .collect();
+        assert_eq!(showdown(&hand.diffs), expected);
+
+        // The button bets the river and gets called, so it shows first even if it loses
+        let button = vec![BetResp::Bet(2), BetResp::Bet(0), BetResp::Bet(0), BetResp::Bet(4)];
+        let hand = play_scripted_hand(texas_hold_em(), blinds, HandExtras::default(), vec![button, Vec::new(), Vec::new()], &[0, 1, 2], standard_deck().clone()).await;
+        let winners = high_winners(&hand.diffs);
+        let expected: Vec<(PlayerId, bool)> = showdown_order(Some(0), 3).into_iter()
+            .map(|role| (role.to_string(), role == 0 || winners.contains(&role.to_string())))
+            .collect();
+        assert_eq!(showdown(&hand.diffs), expected);
+    }
+
+    #[tokio::test]
+    async fn test_show_uncontested() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        // The big blind folds to a bet on the river and the button shows its hand
+        let button = vec![BetResp::Bet(2), BetResp::Bet(0), BetResp::Bet(0), BetResp::Bet(4)];
+        let big_blind = vec![BetResp::Bet(2), BetResp::Bet(0), BetResp::Bet(0), BetResp::Bet(0), BetResp::Fold];
+        let hand = play_scripted_hand(omaha_hi_lo(), blinds, HandExtras::default(), vec![button, big_blind], &[], standard_deck().clone()).await;
+        assert_eq!(hand.result.winnings[&0], 2);
+        let mut community = CardTuple::new();
+        for diff in &hand.diffs {
+            if let PokerViewDiff::CommunityDraw{drawn} = diff {
+                for cvs in drawn {
+                    if let CardViewState::Visible(cs) = cvs {
+                        community.push(cs.card);
+                    }
+                }
+            }
+        }
+        assert_eq!(community.len(), 5);
+        let shows: Vec<_> = hand.diffs.iter().filter_map(|diff| match diff {
+            PokerViewDiff::ShowCards{player, shown, strength, low} => Some((player.clone(), shown.clone(), *strength, *low)),
+            _ => None,
+        }).collect();
+        assert_eq!(shows.len(), 1);
+        let (player, shown, strength, low) = shows[0].clone();
+        assert_eq!(player, "0");
+        let shown: Vec<CardState> = shown.into_iter().filter_map(|(_, cvs)| match cvs {
+            CardViewState::Visible(cs) => Some(cs),
+            _ => None,
+        }).collect();
+        assert_eq!(shown.len(), 4);
+        // Two cards from the hand and three from the board, for the low as well
+        assert_eq!((strength, low), player_best_hands(&omaha_hi_lo(), &shown, community, &vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_passed_out() {
+        // No pairs, straights or flushes, so nobody can open
+        let deck = VecDeck{raw: [1, 2, 3, 5, 6, 7, 9, 10, 11, 12].iter().enumerate().map(|(idx, &rank)| Card{rank, suit: Suit(idx % NUM_SUITS)}).collect()};
+        let bets = vec![vec![BetResp::Bet(10)], vec![BetResp::Bet(10)]];
+        let hand = play_scripted_hand(jacks_or_better(), AnteRule::Ante(1), HandExtras::default(), bets, &[], deck).await;
+        // Without openers a player can only check
+        assert_eq!(hand.offers, vec![vec![(0, 0)], vec![(0, 0)]]);
+        assert!(hand.replaced.is_empty());
+        assert!(hand.diffs.contains(&PokerViewDiff::PassedOut{carried: 2}));
+        assert_eq!(hand.result, HandResult{winnings: vec![(0, -1), (1, -1)].into_iter().collect(), carried_pot: 2});
+
+        // The next deal plays for the antes left in the pot as well
+        let carried = HandExtras{straddle: None, bomb_pot: None, carried_pot: 2};
+        // Both players are dealt flushes, so whoever acts first opens
+        let hand = play_scripted_hand(jacks_or_better(), AnteRule::Ante(1), carried, vec![vec![BetResp::Bet(4)]; 2], &[], standard_deck().clone()).await;
+        let pots: Vec<Chips> = hand.diffs.iter().filter_map(|diff| match diff {
+            PokerViewDiff::Winners(winners) => Some(winners.winners_by_pot.iter().map(|pot| pot.subpot.chips).sum()),
+            _ => None,
+        }).collect();
+        let put_in: Chips = hand.result.winnings.values().filter(|&&chips| chips < 0).map(|chips| -chips).sum();
+        assert_eq!(pots, vec![2 + 2 * put_in]);
+        assert_eq!(hand.result.winnings.values().sum::<Chips>(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_bet() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        // Facing the big blind, a raise short of the minimum or past the player's stack folds instead
+        for bet in [3, 1000] {
+            let hand = play_scripted_hand(texas_hold_em(), blinds.clone(), HandExtras::default(), vec![vec![BetResp::Bet(bet)], Vec::new()], &[], standard_deck().clone()).await;
+            assert_eq!(hand.acted, vec![0]);
+            assert_eq!(hand.result.winnings, vec![(0, -1), (1, 1)].into_iter().collect());
+        }
+        // With nothing to call it's a check
+        let hand = play_scripted_hand(texas_hold_em(), blinds, HandExtras::default(), vec![vec![BetResp::Bet(2)], vec![BetResp::Bet(1)]], &[], standard_deck().clone()).await;
+        assert_eq!(hand.acted, vec![0, 1, 1, 0, 1, 0, 1, 0]);
+        assert!(!hand.diffs.iter().any(|diff| matches!(diff, PokerViewDiff::Fold{..})));
+    }
+
+    #[test]
+    fn test_straddle_offer() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        assert_eq!(straddle_offer(StraddleRule::UnderTheGun, &blinds, 4), Some((3, 4)));
+        assert_eq!(straddle_offer(StraddleRule::Button, &blinds, 4), Some((0, 4)));
+        // Heads-up the button and under the gun both post a blind already
+        assert_eq!(straddle_offer(StraddleRule::UnderTheGun, &blinds, 2), None);
+        assert_eq!(straddle_offer(StraddleRule::Button, &blinds, 2), None);
+        assert_eq!(straddle_offer(StraddleRule::Button, &AnteRule::Ante(1), 4), None);
+    }
+
+    #[tokio::test]
+    async fn test_straddle_action_order() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        // The straddler acts last preflop
+        let utg = HandExtras{straddle: Some((3, 4)), bomb_pot: None, carried_pot: 0};
+        let (acted, _) = play_scripted(texas_hold_em(), blinds.clone(), utg, vec![Vec::new(); 4]).await;
+        assert_eq!(acted, vec![0, 1, 2, 3, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0]);
+
+        let button = HandExtras{straddle: Some((0, 4)), bomb_pot: None, carried_pot: 0};
+        let (acted, _) = play_scripted(texas_hold_em(), blinds, button, vec![Vec::new(); 4]).await;
+        assert_eq!(acted, vec![1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_bomb_pot() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        let bomb_pot = HandExtras{straddle: None, bomb_pot: Some(5), carried_pot: 0};
+        let (acted, shown) = play_scripted(texas_hold_em(), blinds, bomb_pot, vec![Vec::new(); 3]).await;
+        // No blinds and no preflop betting
+        assert_eq!(acted, vec![1, 2, 0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(shown.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_all_in_reveal() {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        // The button shoves preflop and gets called, so nobody acts again and each hand is shown once
+        let (acted, shown) = play_scripted(texas_hold_em(), blinds, HandExtras::default(), vec![vec![BetResp::Bet(100)], Vec::new()]).await;
+        assert_eq!(acted, vec![0, 1]);
+        assert_eq!(shown, vec!["0", "1"]);
+    }
+
+    #[test]
+    fn test_variant_max_players() {
+        let num_cards = standard_deck().raw.len();
+        assert_eq!(variant_max_players(&texas_hold_em(), num_cards), 23);
+        assert_eq!(variant_max_players(&five_card_draw(), num_cards), 6);
+        // Eight players get six cards each, then share a community card on seventh street
+        assert_eq!(variant_max_players(&seven_card_stud(), num_cards), 8);
+        // Only the first draw has to come from the stub. The rest reshuffle the discards
+        assert_eq!(variant_max_players(&deuce_to_seven_draw(3), num_cards), 5);
+        assert_eq!(variant_max_players(&badugi(), num_cards), 6);
+        // Heads-up, each player's one discard is all the other can draw the second time
+        let mut two_draws = deuce_to_seven_draw(2);
+        for (round, max) in two_draws.rules.iter_mut().filter(|r| matches!(r, Round::Replace{..})).zip([1, 2]) {
+            if let Round::Replace{rule, max_possible_replace, ..} = round {
+                *rule = ReplaceRule::Fixed(max);
+                *max_possible_replace = max;
+            }
+        }
+        assert_eq!(variant_max_players(&two_draws, 12), 1);
+        assert_eq!(variant_max_players(&two_draws, 13), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stand_pat() {
+        let hand = play_scripted_hand(deuce_to_seven_draw(1), AnteRule::Ante(1), HandExtras::default(), vec![Vec::new(); 3], &[], standard_deck().clone()).await;
+        // Draws start left of the button
+        assert_eq!(hand.replaced, vec![1, 2, 0]);
+        let stood_pat: Vec<PlayerId> = hand.diffs.iter().filter_map(|diff| match diff {
+            PokerViewDiff::StandPat{player} => Some(player.clone()),
+            _ => None,
+        }).collect();
+        assert_eq!(stood_pat, vec!["1", "2", "0"]);
+        assert!(!hand.diffs.iter().any(|diff| matches!(diff, PokerViewDiff::Replace{..})));
+    }
+
+    #[tokio::test]
+    async fn test_draw_order() {
+        use Facing::*;
+        // One card up, and the best one showing draws first
+        let mut variant = deuce_to_seven_draw(1);
+        variant.ranking = HandRanking::High;
+        for round in variant.rules.iter_mut() {
+            match round {
+                Round::DrawToHand{facing} => facing[0] = FaceUp,
+                Round::Replace{order, ..} => *order = ActionOrder::BestVisibleHand,
+                _ => {}
+            }
+        }
+        let hand = play_scripted_hand(variant, AnteRule::Ante(1), HandExtras::default(), vec![Vec::new(); 3], &[], standard_deck().clone()).await;
+        // The king of the last suit comes off the top of the deck
+        let king = hand.diffs.iter().find_map(|diff| match diff {
+            PokerViewDiff::Draw{player, drawn} if drawn.contains(&CardViewState::Visible(CardState{card: Card{rank: 12, suit: Suit(3)}, facing: FaceUp})) => Some(player.parse::<PlayerRole>().unwrap()),
+            _ => None,
+        }).unwrap();
+        assert_eq!(hand.replaced, vec![king, (king + 1) % 3, (king + 2) % 3]);
+    }
+
+    #[test]
+    fn test_reshuffle() {
+        let card = |rank| Card{rank, suit: Suit(0)};
+        let mut deck = VecDeck{raw: vec![card(1)]};
+        deck.reshuffle(vec![card(2), card(3)]).unwrap();
+        // What was left of the stub is dealt before the discards
+        assert_eq!(deck.draw().unwrap(), card(1));
+        let mut rest = vec![deck.draw().unwrap(), deck.draw().unwrap()];
+        rest.sort();
+        assert_eq!(rest, vec![card(2), card(3)]);
+        assert!(deck.draw().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stud_community_card() {
+        let (acted, shown) = play_scripted(seven_card_stud(), AnteRule::Ante(1), HandExtras::default(), vec![Vec::new(); 8]).await;
+        // Everyone checks all five streets and then shows
+        assert_eq!(acted.len(), 8 * 5);
+        assert_eq!(shown.len(), 8);
+    }
 }
@@ -35,9 +35,9 @@ pub struct PokerHttpClient<P> {
 pub type PokerClientResult = Result<(), String>;
 
 impl<P: PlayerInputSource + PokerViewClient> PokerHttpClient<P> {
-    async fn bet(&mut self, min_bet: Chips, call_amount: Chips) -> BetResp {
+    async fn bet(&mut self, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp {
         let mut input_lock = self.input.lock().unwrap();
-        input_lock.bet(min_bet, call_amount).await
+        input_lock.bet(call_amount, min_bet, max_bet).await
     }
 
     pub async fn start(&mut self) -> PokerClientResult {
@@ -79,20 +79,40 @@ impl<P: PlayerInputSource + PokerViewClient> PokerHttpClient<P> {
 
                 if let Some(action) = server_player.action_requested {
                     let (path, json) = match action {
-                        ServerActionRequest::Bet { min_bet, call_amount } => {
-                            println!("Requested bet: {} {}", min_bet, call_amount);
-                            let resp = self.bet(call_amount, min_bet).await;
+                        ServerActionRequest::Bet { min_bet, max_bet, call_amount, .. } => {
+                            println!("Requested bet: {} {} {}", min_bet, max_bet, call_amount);
+                            let resp = self.bet(call_amount, min_bet, max_bet).await;
                             ("/bet", serde_json::to_vec(&resp).unwrap())
                         },
-                        ServerActionRequest::Replace{max_can_replace} => {
+                        ServerActionRequest::Replace{max_can_replace, ..} => {
                             let mut input_lock = self.input.lock().unwrap();
                             let resp = input_lock.replace(max_can_replace).await;
                             ("/replace", serde_json::to_vec(&resp).unwrap())
                         },
-                        ServerActionRequest::DealersChoice{variants} => {
+                        ServerActionRequest::DealersChoice{variants, ..} => {
                             let mut input_lock = self.input.lock().unwrap();
                             let resp = input_lock.dealers_choice(variants).await;
                             ("/dealers_choice", serde_json::to_vec(&resp).unwrap())
+                        },
+                        ServerActionRequest::Straddle{amount, ..} => {
+                            let mut input_lock = self.input.lock().unwrap();
+                            let resp = input_lock.straddle(amount).await;
+                            ("/straddle", serde_json::to_vec(&resp).unwrap())
+                        },
+                        ServerActionRequest::RunIt{times, ..} => {
+                            let mut input_lock = self.input.lock().unwrap();
+                            let resp = input_lock.run_it(times).await;
+                            ("/run_it", serde_json::to_vec(&resp).unwrap())
+                        },
+                        ServerActionRequest::ShowOrMuck{..} => {
+                            let mut input_lock = self.input.lock().unwrap();
+                            let resp = input_lock.show_or_muck().await;
+                            ("/show_or_muck", serde_json::to_vec(&resp).unwrap())
+                        },
+                        ServerActionRequest::ShowUncontested{num_cards, ..} => {
+                            let mut input_lock = self.input.lock().unwrap();
+                            let resp = input_lock.show_uncontested(num_cards).await;
+                            ("/show_uncontested", serde_json::to_vec(&resp).unwrap())
                         }
                     };
                     let mut req = Request::builder()
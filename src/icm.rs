@@ -0,0 +1,138 @@
+use crate::game::*;
+
+use ts_rs::{TS, export};
+use serde::{Serialize, Deserialize};
+
+// The table of finishing orders doubles with every player
+pub const MAX_ICM_PLAYERS: usize = 20;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct IcmRequest {
+    pub stacks: Vec<Chips>,
+    // Prize for first place, second place, and so on
+    pub payouts: Vec<Chips>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct IcmEquity {
+    pub player_id: PlayerId,
+    pub chips: Chips,
+    pub equity: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct IcmDecision {
+    pub fold: f64,
+    pub call: f64,
+}
+
+// Independent Chip Model: each player with chips finishes first with probability proportional
+// to their stack, and each later place is decided the same way among the rest. Players without
+// chips split the last places
+pub fn icm_equity(stacks: &[Chips], payouts: &[Chips]) -> Result<Vec<f64>, String> {
+    if stacks.iter().any(|&s| s < 0) {
+        return Err("Stacks can't be negative".to_string());
+    }
+    let alive: Vec<usize> = (0..stacks.len()).filter(|&i| stacks[i] > 0).collect();
+    if alive.len() > MAX_ICM_PLAYERS {
+        return Err(format!("ICM supports at most {} players with chips", MAX_ICM_PLAYERS));
+    }
+    let payout = |place: usize| payouts.get(place).copied().unwrap_or(0) as f64;
+    let mut retval = vec![0f64; stacks.len()];
+
+    let busted = stacks.len() - alive.len();
+    if busted > 0 {
+        let shared = (alive.len()..stacks.len()).map(payout).sum::<f64>() / busted as f64;
+        for i in (0..stacks.len()).filter(|&i| stacks[i] == 0) {
+            retval[i] = shared;
+        }
+    }
+
+    let places = std::cmp::min(payouts.len(), alive.len());
+    let total: Chips = stacks.iter().sum();
+    // Chance that the players in each set of alive players take the top places, in any order
+    let mut prob = vec![0f64; 1 << alive.len()];
+    prob[0] = 1.0;
+    for mask in 0..prob.len() {
+        let place = mask.count_ones() as usize;
+        if prob[mask] == 0.0 || place >= places {
+            continue;
+        }
+        let placed: Chips = (0..alive.len()).filter(|&a| mask & (1 << a) != 0).map(|a| stacks[alive[a]]).sum();
+        for a in (0..alive.len()).filter(|&a| mask & (1 << a) == 0) {
+            let p = prob[mask] * stacks[alive[a]] as f64 / (total - placed) as f64;
+            retval[alive[a]] += p * payout(place);
+            prob[mask | (1 << a)] += p;
+        }
+    }
+    Ok(retval)
+}
+
+// Equity for `player` of folding or calling `call` more chips against `opponent`, who is all in.
+// Stacks are the chips each player has behind, and `pot` is everything already in the middle
+// that the winner takes, so any part of the opponent's bet that can't be called should be
+// returned to their stack first
+pub fn call_or_fold(stacks: &[Chips], payouts: &[Chips], player: usize, opponent: usize, call: Chips, pot: Chips, win_prob: f64) -> Result<IcmDecision, String> {
+    if call > stacks[player] {
+        return Err("The call is more than the player has".to_string());
+    }
+    let mut fold = stacks.to_vec();
+    fold[opponent] += pot;
+
+    let mut win = stacks.to_vec();
+    win[player] += pot;
+    let mut lose = stacks.to_vec();
+    lose[player] -= call;
+    lose[opponent] += pot + call;
+
+    let equity = |stacks: &[Chips]| icm_equity(stacks, payouts).map(|e| e[player]);
+    Ok(IcmDecision {
+        fold: equity(&fold)?,
+        call: win_prob * equity(&win)? + (1.0 - win_prob) * equity(&lose)?,
+    })
+}
+
+mod test {
+    use crate::icm::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_icm_equity() {
+        let equity = icm_equity(&[1000, 1000], &[70, 30]).unwrap();
+        assert_close(equity[0], 50.0);
+        assert_close(equity[1], 50.0);
+
+        // The chip leader's equity is less than their share of the chips
+        let equity = icm_equity(&[50, 30, 20], &[50, 30, 20]).unwrap();
+        assert_close(equity[0], 38.39);
+        assert_close(equity.iter().sum(), 100.0);
+
+        // Winner takes all is just the chip share
+        let equity = icm_equity(&[300, 100], &[100]).unwrap();
+        assert_close(equity[0], 75.0);
+
+        // Busted players split the last places
+        assert_eq!(icm_equity(&[100, 0, 0], &[60, 30, 10]).unwrap(), vec![60.0, 20.0, 20.0]);
+        assert!(icm_equity(&[100, -1], &[100]).is_err());
+    }
+
+    #[test]
+    fn test_call_or_fold() {
+        // The opponent shoved 1000 into an even three way final table. A coin flip is even in
+        // chips, but folding is worth more
+        let stacks = [1000, 0, 1000];
+        let decision = call_or_fold(&stacks, &[50, 30, 20], 0, 1, 1000, 1000, 0.5).unwrap();
+        assert_close(decision.call, 31.67);
+        assert_close(decision.fold, 33.33);
+
+        let decision = call_or_fold(&stacks, &[50, 30, 20], 0, 1, 1000, 1000, 0.6).unwrap();
+        assert!(decision.call > decision.fold);
+        assert!(call_or_fold(&stacks, &[50, 30, 20], 0, 1, 2000, 1000, 0.5).is_err());
+    }
+}
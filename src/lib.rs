@@ -16,13 +16,18 @@ pub mod bitcard;
 pub mod special_card;
 //mod hand;
 pub mod game;
+pub mod variant_file;
 pub mod gamestate;
 //mod state_iter;
 pub mod auth;
 pub mod table;
+pub mod settlement;
+pub mod blind_schedule;
+pub mod tournament;
 pub mod viewstate;
 pub mod cmdline;
 pub mod bot;
+pub mod icm;
 pub mod bot_always_call;
 pub mod bot_easy;
 pub mod bot_medium;
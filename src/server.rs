@@ -13,6 +13,10 @@ use crate::bot_always_call::*;
 use crate::bot_easy::*;
 use crate::bot_medium::*;
 use crate::static_config::*;
+use crate::variant_file::*;
+use crate::blind_schedule::*;
+use crate::tournament::*;
+use crate::icm::*;
 use crate::static_files::*;
 
 use ts_rs::{TS, export};
@@ -46,6 +50,10 @@ use hyper::server::accept;
 use hyper_rustls;
 use futures_util::future::TryFutureExt;
 
+// How long players get to answer optional questions, like whether to show, straddle or run it
+// more than once. Nobody is sat out for not answering, the question is just declined
+const ASK_SECS: u64 = 10;
+
 pub struct PlayerConnection {
     pub input_source: Arc<GameServerPlayerInputSource>
 }
@@ -56,16 +64,50 @@ pub struct PlayerConnection {
 pub enum ServerActionRequest {
     Bet {
         call_amount: Chips,
-        min_bet: Chips
+        min_bet: Chips,
+        max_bet: Chips,
+        #[serde(default)]
+        timer: Option<ActionClock>,
     },
     Replace {
         max_can_replace: usize,
+        #[serde(default)]
+        timer: Option<ActionClock>,
     },
     DealersChoice {
         variants: Vec<PokerVariantDesc>,
+        #[serde(default)]
+        timer: Option<ActionClock>,
+    },
+    Straddle {
+        amount: Chips,
+        #[serde(default)]
+        timer: Option<ActionClock>,
+    },
+    RunIt {
+        times: usize,
+        #[serde(default)]
+        timer: Option<ActionClock>,
+    },
+    ShowOrMuck {
+        #[serde(default)]
+        timer: Option<ActionClock>,
+    },
+    ShowUncontested {
+        num_cards: usize,
+        #[serde(default)]
+        timer: Option<ActionClock>,
     },
 }
 
+// Time left when the action was requested. Clients count down from when they receive it
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct ActionClock {
+    pub action_ms: u64,
+    pub time_bank_ms: u64,
+}
+
 #[derive(Eq, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
 #[derive(TS)]
 #[serde(tag = "kind", content="data")]
@@ -81,19 +123,30 @@ pub enum AnteRuleChangeDesc {
     },
 }
 
-#[derive(Eq, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 #[derive(TS)]
 pub struct AnteRuleDesc {
     starting_value: Chips,
     blinds: bool,
+    // Only used without blinds. The bring-in is twice the ante
+    #[serde(default)]
+    bring_in: bool,
     change: AnteRuleChangeDesc,
+    // Explicit levels, used instead of starting_value and change
+    #[serde(default)]
+    schedule: Option<BlindSchedule>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[derive(TS)]
 pub struct ServerPlayer {
     pub viewstate: Option<PokerViewState>,
-    pub action_requested: Option<ServerActionRequest>
+    pub action_requested: Option<ServerActionRequest>,
+    #[serde(default)]
+    pub sitting_out: bool,
+    // The table the player is seated at, which changes when a tournament moves them
+    #[serde(default)]
+    pub table_id: Option<TableId>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -108,6 +161,7 @@ pub struct ServerUpdate {
 }
 
 pub type TableId = usize;
+pub type TournamentId = usize;
 pub type GameServerId = u64;
 
 pub struct GameServerTable {
@@ -116,10 +170,20 @@ pub struct GameServerTable {
     players: Mutex<HashMap<PlayerId, Arc<GameServerPlayerInputSource>>>,
     log_update_channel_r: fold_channel::Receiver<Vec<PokerGlobalViewDiff<PlayerId>>>,
     bots: Mutex<Vec<Arc<BotInputSource>>>,
+    // Tournament tables only seat the players the tournament puts there
+    open_seating: bool,
+}
+
+pub struct GameServerTournament {
+    tournament: Tournament,
+    tables: Mutex<BTreeMap<TableId, Arc<GameServerTable>>>,
+    // Registered bots, seated when the tournament starts
+    bots: Mutex<HashMap<PlayerId, Arc<dyn Bot>>>,
 }
 
 pub struct GameServer {
     tables: Mutex<BTreeMap<TableId, Arc<GameServerTable>>>,
+    tournaments: Mutex<BTreeMap<TournamentId, Arc<GameServerTournament>>>,
     id_counter: IdCounter,
     auth: Mutex<RandomTokenAuthMap<GameServerId>>,
     static_files: StaticFiles,
@@ -137,6 +201,19 @@ pub struct GameServerPlayerInputSource {
     replace_rx: watch::Receiver<Option<ReplaceResp>>,
     dealers_choice_tx: watch::Sender<DealersChoiceResp>,
     dealers_choice_rx: watch::Receiver<DealersChoiceResp>,
+    straddle_tx: watch::Sender<Option<bool>>,
+    straddle_rx: watch::Receiver<Option<bool>>,
+    run_it_tx: watch::Sender<Option<bool>>,
+    run_it_rx: watch::Receiver<Option<bool>>,
+    show_or_muck_tx: watch::Sender<Option<bool>>,
+    show_or_muck_rx: watch::Receiver<Option<bool>>,
+    show_uncontested_tx: watch::Sender<Option<ShowResp>>,
+    show_uncontested_rx: watch::Receiver<Option<ShowResp>>,
+    timer: ActionTimer,
+    time_bank: Mutex<std::time::Duration>,
+    // Set when the player runs out of time. Their actions are made for them until they sit back in
+    sitting_out: Mutex<bool>,
+    table_id: Mutex<Option<TableId>>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -144,15 +221,31 @@ pub struct GameServerPlayerInputSource {
 pub struct ServerTableParameters {
     table_config: TableConfig,
     ante_rule: AnteRuleDesc,
+    #[serde(default)]
+    betting: BettingStructure,
+    #[serde(default)]
+    rake: Option<Rake>,
+    #[serde(default)]
+    straddle: Option<StraddleRule>,
+    #[serde(default)]
+    bomb_pot: Option<BombPotRule>,
+    #[serde(default)]
+    run_it: Option<usize>,
+    #[serde(default)]
+    all_in_pause_ms: u64,
 }
 
 impl GameServerPlayerInputSource {
-    fn new() -> GameServerPlayerInputSource {
+    fn new(timer: ActionTimer) -> GameServerPlayerInputSource {
         let (update_tx, update_rx) = watch::channel(None);
         let (action_tx, action_rx) = watch::channel(None);
         let (bet_tx, bet_rx) = watch::channel(None);
         let (replace_tx, replace_rx) = watch::channel(None);
         let (dealers_choice_tx, dealers_choice_rx) = watch::channel(DealersChoiceResp::default());
+        let (straddle_tx, straddle_rx) = watch::channel(None);
+        let (run_it_tx, run_it_rx) = watch::channel(None);
+        let (show_or_muck_tx, show_or_muck_rx) = watch::channel(None);
+        let (show_uncontested_tx, show_uncontested_rx) = watch::channel(None);
         GameServerPlayerInputSource {
             update_tx,
             update_rx,
@@ -164,6 +257,18 @@ impl GameServerPlayerInputSource {
             replace_rx,
             dealers_choice_tx,
             dealers_choice_rx,
+            straddle_tx,
+            straddle_rx,
+            run_it_tx,
+            run_it_rx,
+            show_or_muck_tx,
+            show_or_muck_rx,
+            show_uncontested_tx,
+            show_uncontested_rx,
+            timer,
+            time_bank: Mutex::new(std::time::Duration::from_secs(timer.time_bank_secs)),
+            sitting_out: Mutex::new(false),
+            table_id: Mutex::new(None),
         }
     }
     fn server_player(&self) -> ServerPlayer {
@@ -171,59 +276,193 @@ impl GameServerPlayerInputSource {
         let update = self.update_rx.borrow().clone();
         let viewstate = update.map(|u| u.viewstate);
         let action_requested = self.action_rx.borrow().clone();
-        ServerPlayer { viewstate, action_requested }
+        let sitting_out = self.sitting_out();
+        let table_id = *self.table_id.lock().unwrap();
+        ServerPlayer { viewstate, action_requested, sitting_out, table_id }
+    }
+
+    fn clock(&self) -> Option<ActionClock> {
+        self.timer.action_secs.map(|secs| ActionClock {
+            action_ms: secs * 1000,
+            time_bank_ms: self.time_bank.lock().unwrap().as_millis() as u64,
+        })
+    }
+
+    // Waits for the player's response, using up their time bank once the action timer runs out.
+    // Returns None and sits the player out if both run out
+    async fn timed<T>(&self, response: impl Future<Output = T>) -> Option<T> {
+        let action = match self.timer.action_secs {
+            Some(secs) => std::time::Duration::from_secs(secs),
+            None => return Some(response.await),
+        };
+        let time_bank = *self.time_bank.lock().unwrap();
+        let start = tokio::time::Instant::now();
+        match tokio::time::timeout(action + time_bank, response).await {
+            Ok(resp) => {
+                let used = start.elapsed().saturating_sub(action);
+                *self.time_bank.lock().unwrap() = time_bank.saturating_sub(used);
+                Some(resp)
+            },
+            Err(_) => {
+                println!("Player ran out of time");
+                *self.time_bank.lock().unwrap() = std::time::Duration::from_secs(0);
+                *self.sitting_out.lock().unwrap() = true;
+                None
+            }
+        }
+    }
+
+    // Waits a little while for an answer to an optional question, without using up the player's time
+    async fn ask<T: Clone>(&self, request: ServerActionRequest, rx: &watch::Receiver<Option<T>>) -> Option<T> {
+        let mut rx = rx.clone();
+        rx.borrow_and_update();
+        self.action_tx.send(Some(request));
+        let retval = tokio::time::timeout(std::time::Duration::from_secs(ASK_SECS), async {
+            loop {
+                rx.changed().await;
+                if let Some(retval) = rx.borrow().clone() {
+                    return retval;
+                }
+            }
+        }).await.ok();
+        self.action_tx.send(None);
+        retval
+    }
+
+    fn ask_clock() -> Option<ActionClock> {
+        Some(ActionClock {
+            action_ms: ASK_SECS * 1000,
+            time_bank_ms: 0,
+        })
+    }
+
+    fn check_or_fold(&self, call_amount: Chips) -> BetResp {
+        let bet_this_round = self.update_rx.borrow().as_ref().map(|u| u.viewstate.bet_this_round.get(&u.viewstate.role).copied().unwrap_or(0)).unwrap_or(0);
+        check_or_fold(call_amount, bet_this_round)
     }
 }
 
 #[async_trait]
 impl PlayerInputSource for GameServerPlayerInputSource {
-    async fn bet(&self, call_amount: Chips, min_bet: Chips) -> BetResp {
+    async fn bet(&self, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp {
+        if self.sitting_out() {
+            return self.check_or_fold(call_amount);
+        }
         let mut rx = self.bet_rx.clone();
         rx.borrow_and_update();
         self.action_tx.send(Some(ServerActionRequest::Bet {
                 call_amount,
-                min_bet
+                min_bet,
+                max_bet,
+                timer: self.clock(),
         }));
-        loop {
-            rx.changed().await;
-            if let Some(resp) = *rx.borrow() {
-                self.action_tx.send(None);
-                return resp;
+        let resp = self.timed(async {
+            loop {
+                rx.changed().await;
+                if let Some(resp) = *rx.borrow() {
+                    return resp;
+                }
             }
-        }
+        }).await;
+        self.action_tx.send(None);
+        resp.unwrap_or_else(|| self.check_or_fold(call_amount))
     }
 
     async fn replace(&self, max_can_replace: usize) -> ReplaceResp {
+        if self.sitting_out() {
+            return Vec::new();
+        }
         let mut rx = self.replace_rx.clone();
         rx.borrow_and_update();
         self.action_tx.send(Some(ServerActionRequest::Replace {
-            max_can_replace
+            max_can_replace,
+            timer: self.clock(),
         }));
-        loop {
-            rx.changed().await;
-            if let Some(retval) = rx.borrow().clone() {
-                self.action_tx.send(None);
-                return retval;
+        let retval = self.timed(async {
+            loop {
+                rx.changed().await;
+                if let Some(retval) = rx.borrow().clone() {
+                    return retval;
+                }
             }
-        }
+        }).await;
+        self.action_tx.send(None);
+        retval.unwrap_or_default()
     }
 
     async fn dealers_choice(&self, variants: Vec<PokerVariantDesc>) -> DealersChoiceResp {
+        if self.sitting_out() {
+            return DealersChoiceResp::default();
+        }
         let mut rx = self.dealers_choice_rx.clone();
         rx.borrow_and_update();
         self.action_tx.send(Some(ServerActionRequest::DealersChoice {
-            variants
+            variants,
+            timer: self.clock(),
         }));
-        rx.changed().await;
-        let retval = rx.borrow().clone();
+        let retval = self.timed(async {
+            rx.changed().await;
+            rx.borrow().clone()
+        }).await;
         self.action_tx.send(None);
-        retval
+        retval.unwrap_or_else(DealersChoiceResp::default)
+    }
+
+    async fn straddle(&self, amount: Chips) -> bool {
+        if self.sitting_out() {
+            return false;
+        }
+        let request = ServerActionRequest::Straddle {
+            amount,
+            timer: Self::ask_clock(),
+        };
+        self.ask(request, &self.straddle_rx).await.unwrap_or(false)
+    }
+
+    async fn run_it(&self, times: usize) -> bool {
+        if self.sitting_out() {
+            return false;
+        }
+        let request = ServerActionRequest::RunIt {
+            times,
+            timer: Self::ask_clock(),
+        };
+        self.ask(request, &self.run_it_rx).await.unwrap_or(false)
+    }
+
+    async fn show_or_muck(&self) -> bool {
+        if self.sitting_out() {
+            return false;
+        }
+        let request = ServerActionRequest::ShowOrMuck {
+            timer: Self::ask_clock(),
+        };
+        self.ask(request, &self.show_or_muck_rx).await.unwrap_or(false)
+    }
+
+    async fn show_uncontested(&self, num_cards: usize) -> ShowResp {
+        if self.sitting_out() {
+            return ShowResp::Muck;
+        }
+        let request = ServerActionRequest::ShowUncontested {
+            num_cards,
+            timer: Self::ask_clock(),
+        };
+        self.ask(request, &self.show_uncontested_rx).await.unwrap_or(ShowResp::Muck)
     }
 
     fn update(&self, update: PokerViewUpdate) {
         //println!("Update sent");
         self.update_tx.send(Some(update));
     }
+
+    fn sitting_out(&self) -> bool {
+        *self.sitting_out.lock().unwrap()
+    }
+
+    fn set_sitting_out(&self, sitting_out: bool) {
+        *self.sitting_out.lock().unwrap() = sitting_out;
+    }
 }
 
 fn special_card_option_html(groups: &[SpecialCardGroup]) -> String {
@@ -264,16 +503,54 @@ impl GameServerTable {
             }
         }
     }
+
+    // Like GameServer::get_player, but without joining the table
+    fn find_player(&self, player_id: &PlayerId) -> Option<Arc<GameServerPlayerInputSource>> {
+        self.players.lock().unwrap().get(player_id).cloned()
+    }
+}
+
+impl GameServerTournament {
+    // Runs between hands at any of the tournament's tables, and follows moved players to their new table
+    fn after_hand(&self) {
+        let tables = self.tables.lock().unwrap();
+        let moves = self.tournament.after_hand(&tables.iter().map(|(&t, table)| (t, &table.table)).collect());
+        for (player_id, to) in moves {
+            let input_source = tables.values().find_map(|table| table.players.lock().unwrap().get(&player_id).cloned());
+            if let (Some(input_source), Some(to_table)) = (input_source, tables.get(&to)) {
+                *input_source.table_id.lock().unwrap() = Some(to);
+                to_table.players.lock().unwrap().insert(player_id, input_source);
+            }
+        }
+    }
+
+    // Stacks of the players still in at every table but the one `player_id` sits at
+    fn other_stacks(&self, player_id: &PlayerId) -> Vec<Chips> {
+        self.tables.lock().unwrap().values().map(|table| table.table.chips()).filter(|chips| !chips.contains_key(player_id)).flat_map(|chips| chips.into_values()).filter(|&chips| chips > 0).collect()
+    }
+
+    fn viewstate(&self) -> TournamentViewState {
+        let seating = self.tables.lock().unwrap().iter().flat_map(|(&table_id, table)| {
+            table.table.chips().into_keys().map(move |player_id| (player_id, table_id))
+        }).collect();
+        self.tournament.viewstate(seating)
+    }
 }
 
 impl GameServer {
     pub async fn create_and_serve<'a>(table_rules: TableRules) {
         let static_config = read_static_config();
+        if let Some(variants_dir) = &static_config.variants_dir {
+            for e in register_variant_dir(std::path::Path::new(variants_dir)) {
+                println!("{}", e);
+            }
+        }
         let static_files = StaticFiles::from_dir_path("ts/static");
         let server = Arc::new({
             //let (log_update_channel_t, log_update_channel_r) = watch::channel(());
             GameServer {
                 tables: Mutex::new(BTreeMap::new()),
+                tournaments: Mutex::new(BTreeMap::new()),
                 id_counter: IdCounter::new(),
                 auth: Mutex::new(RandomTokenAuthMap::new(512)),
                 static_files,
@@ -347,8 +624,7 @@ impl GameServer {
 
     }
         
-    pub async fn notify_player<'a>(&'a self, table: Arc<GameServerTable>, player_id: PlayerId, start_from: usize, known_action_requested: Option<ServerActionRequest>) -> (Vec<PokerLogUpdate>, ServerPlayer) {
-        let player = self.get_player(&table, player_id.clone()).unwrap();
+    pub async fn notify_player<'a>(&'a self, table: Arc<GameServerTable>, player: Arc<GameServerPlayerInputSource>, player_id: PlayerId, start_from: usize, known_action_requested: Option<ServerActionRequest>) -> (Vec<PokerLogUpdate>, ServerPlayer) {
         let mut action_rx = player.action_tx.subscribe();
         let mut log_update_channel = table.log_update_channel_r.clone();
         let mut table_view_rx = table.table.table_view_rx.clone();
@@ -402,15 +678,16 @@ impl GameServer {
         let mut players = table.players.lock().unwrap();
         match players.entry(player_id.clone()) {
             Occupied(e) => Some(e.get().clone()),
+            Vacant(_) if !table.open_seating => None,
             Vacant(v) => {
-                let new_player = Arc::new(GameServerPlayerInputSource::new());
+                let new_player = Arc::new(GameServerPlayerInputSource::new(table.table.config().timer));
                 match table.table.join(player_id, new_player.clone()) {
                     Ok(()) => {
                         v.insert(new_player.clone());
                         Some(new_player)
                     },
-                    Err(JoinError::Full) => None,
-                    Err(JoinError::AlreadyJoined) => panic!("Impossible!")
+                    // Players who left mid-hand stay seated until it's over
+                    Err(JoinError::Full) | Err(JoinError::Started) | Err(JoinError::AlreadyJoined) => None,
                 }
             }
         }
@@ -421,24 +698,58 @@ impl GameServer {
         self.tables.lock().unwrap().get(&table_id).cloned()
     }
 
-    fn create_table(&self, params: ServerTableParameters) -> Result<TableId, String> {
-        let ServerTableParameters{table_config, ante_rule} = params;
-        if !table_config.is_valid() {
-            return Err("Invalid table config".to_string());
-        }
+    fn tournament_from_params(&self, params: &HashMap<String, String>) -> Option<Arc<GameServerTournament>> {
+        let tournament_id: TournamentId = params.get("tournament_id")?.parse::<usize>().ok()?;
+        self.tournaments.lock().unwrap().get(&tournament_id).cloned()
+    }
+
+    fn add_table(&self, table: Table, open_seating: bool) -> (TableId, Arc<GameServerTable>) {
         let gametable = Arc::new({
-            let starting_rules = ante_rule.starting_rules();
-            let table = Table::new(table_config, starting_rules, ante_rule.rule_fn());
             let players = Mutex::new(HashMap::new());
             let log_update_channel_r = table.spectator_rx.clone();
             let bots = Mutex::new(Vec::new());
             let player_id_map = Mutex::new(HashMap::new());
-            GameServerTable { table, player_id_map, players, log_update_channel_r, bots }
+            GameServerTable { table, player_id_map, players, log_update_channel_r, bots, open_seating }
         });
 
         let mut tables = self.tables.lock().unwrap();
         let table_id = tables.keys().max().copied().map(|tid| tid+1).unwrap_or(0);
         tables.insert(table_id, gametable.clone());
+        (table_id, gametable)
+    }
+
+    fn create_table(&self, params: ServerTableParameters) -> Result<TableId, String> {
+        let ServerTableParameters{table_config, ante_rule, betting, rake, straddle, bomb_pot, run_it, all_in_pause_ms} = params;
+        if !table_config.is_valid() {
+            return Err("Invalid table config".to_string());
+        }
+        if !betting.is_valid() {
+            return Err("Invalid betting structure".to_string());
+        }
+        if !rake.map(|r| r.is_valid()).unwrap_or(true) {
+            return Err("Invalid rake".to_string());
+        }
+        if !bomb_pot.map(|b| b.is_valid()).unwrap_or(true) {
+            return Err("Invalid bomb pot".to_string());
+        }
+        if run_it.map(|times| times < 2).unwrap_or(false) {
+            return Err("Must run it at least twice".to_string());
+        }
+        if !ante_rule.schedule.as_ref().map(|s| s.is_valid()).unwrap_or(true) {
+            return Err("Invalid blind schedule".to_string());
+        }
+        let mut starting_rules = ante_rule.starting_rules();
+        starting_rules.betting = betting;
+        starting_rules.rake = rake;
+        starting_rules.straddle = straddle;
+        starting_rules.bomb_pot = bomb_pot;
+        starting_rules.run_it = run_it;
+        starting_rules.all_in_pause_ms = all_in_pause_ms;
+        let table = Table::new(table_config, starting_rules, ante_rule.rule_fn());
+        if let Some(schedule) = ante_rule.schedule {
+            table.set_schedule(schedule);
+        }
+        let (table_id, gametable) = self.add_table(table, true);
 
         tokio::spawn(async move {
             loop {
@@ -456,6 +767,77 @@ impl GameServer {
         Ok(table_id)
     }
 
+    fn create_tournament(&self, config: TournamentConfig) -> Result<TournamentId, String> {
+        if !config.is_valid() {
+            return Err("Invalid tournament config".to_string());
+        }
+        let mut tournaments = self.tournaments.lock().unwrap();
+        let tournament_id = tournaments.keys().max().copied().map(|tid| tid+1).unwrap_or(0);
+        tournaments.insert(tournament_id, Arc::new(GameServerTournament {
+            tournament: Tournament::new(config),
+            tables: Mutex::new(BTreeMap::new()),
+            bots: Mutex::new(HashMap::new()),
+        }));
+        Ok(tournament_id)
+    }
+
+    fn start_tournament(&self, tournament: Arc<GameServerTournament>) -> Result<(), String> {
+        let config = &tournament.tournament.config;
+        let ante_rule = AnteRuleDesc {
+            starting_value: 0,
+            blinds: true,
+            bring_in: false,
+            change: AnteRuleChangeDesc::Constant,
+            schedule: Some(config.schedule.clone()),
+        };
+        // Bots weigh their chips against the tournament's payouts
+        let payouts = tournament.tournament.payouts();
+        tournament.tournament.start(|players| {
+            let mut starting_rules = ante_rule.starting_rules();
+            starting_rules.betting = config.betting;
+            let table = Table::new(config.table_config.clone(), starting_rules, ante_rule.rule_fn());
+            table.share_schedule(tournament.tournament.clock.clone());
+            table.set_tournament();
+            let (table_id, gametable) = self.add_table(table, false);
+            for player_id in players {
+                if let Some(bot) = tournament.bots.lock().unwrap().get(player_id).cloned() {
+                    // Weak, because the tournament owns the tables the bot sits at
+                    let weak_tournament = Arc::downgrade(&tournament);
+                    let bot_id = player_id.clone();
+                    let other_stacks = Box::new(move || weak_tournament.upgrade().map(|t| t.other_stacks(&bot_id)).unwrap_or_default());
+                    gametable.table.join(player_id.clone(), Arc::new(BotInputSource::for_tournament(bot, payouts.clone(), other_stacks)));
+                    continue;
+                }
+                let input_source = Arc::new(GameServerPlayerInputSource::new(config.table_config.timer));
+                *input_source.table_id.lock().unwrap() = Some(table_id);
+                if gametable.table.join(player_id.clone(), input_source.clone()).is_ok() {
+                    gametable.players.lock().unwrap().insert(player_id.clone(), input_source);
+                }
+            }
+            tournament.tables.lock().unwrap().insert(table_id, gametable);
+            table_id
+        })?;
+
+        for (table_id, gametable) in tournament.tables.lock().unwrap().clone() {
+            let tournament = tournament.clone();
+            gametable.table.start();
+            tokio::spawn(async move {
+                // A table down to one player keeps polling until players are moved to it or it's broken
+                loop {
+                    tokio::select! {
+                        _ = shutdown_signal() => break,
+                        _ = gametable.table.next_round() => {}
+                    }
+                    tournament.after_hand();
+                    if !tournament.tournament.is_playing(table_id) {
+                        break;
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
     pub async fn serve(&self, req: Request<Body>) -> Result<Response<Body>, Infallible> {
         let mut response = Response::new(Body::empty());
         *response.status_mut() = StatusCode::BAD_REQUEST;
@@ -491,6 +873,85 @@ impl GameServer {
                     }
                 } 
             },
+            (&Method::POST, "/create_tournament") => {
+                if let Ok(config) = serde_json::from_slice::<TournamentConfig>(&hyper::body::to_bytes(req.into_body()).await.unwrap()) {
+                    match self.create_tournament(config) {
+                        Ok(tournament_id) => {
+                            *response.body_mut() = Body::from(serde_json::to_vec(&tournament_id).unwrap());
+                            *response.status_mut() = StatusCode::OK;
+                        },
+                        Err(error) => {
+                            *response.body_mut() = Body::from(error);
+                        }
+                    }
+                }
+            },
+            (&Method::POST, "/register_tournament") => {
+                if let (Some(tournament), Some(player_id)) = (self.tournament_from_params(&params), param_id) {
+                    match tournament.tournament.register(player_id) {
+                        Ok(()) => {
+                            *response.status_mut() = StatusCode::OK;
+                        },
+                        Err(error) => {
+                            *response.body_mut() = Body::from(error);
+                        }
+                    }
+                }
+            },
+            (&Method::POST, "/start_tournament") => {
+                if let Some(tournament) = self.tournament_from_params(&params) {
+                    match self.start_tournament(tournament) {
+                        Ok(()) => {
+                            *response.status_mut() = StatusCode::OK;
+                        },
+                        Err(error) => {
+                            *response.body_mut() = Body::from(error);
+                        }
+                    }
+                }
+            },
+            (&Method::GET, "/tournament") => {
+                if let Some(tournament) = self.tournament_from_params(&params) {
+                    *response.body_mut() = Body::from(serde_json::to_vec(&tournament.viewstate()).unwrap());
+                    *response.status_mut() = StatusCode::OK;
+                }
+            },
+            (&Method::POST, "/icm") => {
+                if let Ok(IcmRequest{stacks, payouts}) = serde_json::from_slice::<IcmRequest>(&hyper::body::to_bytes(req.into_body()).await.unwrap()) {
+                    match icm_equity(&stacks, &payouts) {
+                        Ok(equity) => {
+                            *response.body_mut() = Body::from(serde_json::to_vec(&equity).unwrap());
+                            *response.status_mut() = StatusCode::OK;
+                        },
+                        Err(error) => {
+                            *response.body_mut() = Body::from(error);
+                        }
+                    }
+                }
+            },
+            (&Method::GET, "/icm") => {
+                // Every stack still in a tournament or sit-and-go, for working out a deal
+                let stacks_payouts = if let Some(tournament) = self.tournament_from_params(&params) {
+                    let stacks: HashMap<PlayerId, Chips> = tournament.tables.lock().unwrap().values().flat_map(|table| table.table.chips()).collect();
+                    Some((stacks, tournament.tournament.payouts()))
+                } else {
+                    self.table_from_params(&params).and_then(|table| Some((table.table.chips(), table.table.sit_and_go_payouts()?)))
+                };
+                if let Some((stacks, payouts)) = stacks_payouts {
+                    let mut stacks: Vec<(PlayerId, Chips)> = stacks.into_iter().filter(|(_, chips)| *chips > 0).collect();
+                    stacks.sort();
+                    match icm_equity(&stacks.iter().map(|(_, chips)| *chips).collect::<Vec<Chips>>(), &payouts) {
+                        Ok(equity) => {
+                            let equity: Vec<IcmEquity> = stacks.into_iter().zip(equity).map(|((player_id, chips), equity)| IcmEquity{player_id, chips, equity}).collect();
+                            *response.body_mut() = Body::from(serde_json::to_vec(&equity).unwrap());
+                            *response.status_mut() = StatusCode::OK;
+                        },
+                        Err(error) => {
+                            *response.body_mut() = Body::from(error);
+                        }
+                    }
+                }
+            },
             (&Method::GET, "/index.html") |
             (&Method::GET, "/") |
             (&Method::GET, "") |
@@ -519,7 +980,7 @@ impl GameServer {
                                 player: Some(player),
                                 log: Vec::new(),
                                 slog: None,
-                                table: table.table.table_view_rx.borrow().clone()
+                                table: table.table.current_viewstate()
                             }) {
                                 Ok(v) => {
                                     *response.body_mut() = Body::from(v);
@@ -538,7 +999,7 @@ impl GameServer {
                             player: None,
                             log: Vec::new(),
                             slog: None,
-                            table: table.table.table_view_rx.borrow().clone()
+                            table: table.table.current_viewstate()
                         }) {
                             Ok(v) => {
                                 *response.body_mut() = Body::from(v);
@@ -557,8 +1018,9 @@ impl GameServer {
                     if let Some(start_from) = params.get("start_from").map(|p| p.parse::<usize>().ok()).unwrap_or(None) {
                         if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
                             let known_action_requested = params.get("known_action_requested").map(|t| serde_json::from_str::<Option<ServerActionRequest>>(&t).ok().flatten()).flatten();
-                            if let Some(_) = self.get_player(&table, player_id.clone()) {
-                                let (log, player) = self.notify_player(table.clone(), player_id.clone(), start_from, known_action_requested).await;
+                            // Only /game joins the table, so players who left aren't sat back down by polling
+                            if let Some(input_source) = table.find_player(&player_id) {
+                                let (log, player) = self.notify_player(table.clone(), input_source, player_id.clone(), start_from, known_action_requested).await;
                                 let slog = if Some(true) == params.get("send_string_log").map(|p| p.parse::<i64>().ok().map(|i| i != 0)).flatten() {
                                     Some(log.iter().map(|u| u.log.iter().map(|l| l.to_string()).collect()).collect())
                                 } else {
@@ -570,7 +1032,7 @@ impl GameServer {
                                     player: Some(player),
                                     log,
                                     slog,
-                                    table: table.table.table_view_rx.borrow().clone()
+                                    table: table.table.current_viewstate()
                                 };
                                 //println!("responding: {:#?}", update);
                                 *response.body_mut() = Body::from(serde_json::to_vec(&update).unwrap());
@@ -589,7 +1051,7 @@ impl GameServer {
                                 player: None,
                                 log,
                                 slog,
-                                table: table.table.table_view_rx.borrow().clone()
+                                table: table.table.current_viewstate()
                             };
                             *response.body_mut() = Body::from(serde_json::to_vec(&update).unwrap());
                             *response.status_mut() = StatusCode::OK;
@@ -610,17 +1072,32 @@ impl GameServer {
                 }
             },
             (&Method::POST, "/add_bot") => {
-                if let Some(table) = self.table_from_params(&params) {
-                    let bot_skill = params.get("bot_skill").map(|s| s.parse::<u32>().ok()).flatten().unwrap_or(0);
+                let bot_skill = params.get("bot_skill").map(|s| s.parse::<u32>().ok()).flatten().unwrap_or(0);
+                let new_bot: Arc<dyn Bot> = match bot_skill {
+                    0 => Arc::new(BotAlwaysCall::new()),
+                    1 => Arc::new(BotEasy::new()),
+                    2 => Arc::new(BotMedium::new()),
+                    _ => Arc::new(BotMedium::new()),
+                };
+                if let Some(tournament) = self.tournament_from_params(&params) {
+                    let mut bots = tournament.bots.lock().unwrap();
+                    let player_id = format!("bot{}", bots.len() + 1);
+                    match tournament.tournament.register(player_id.clone()) {
+                        Ok(()) => {
+                            bots.insert(player_id, new_bot);
+                            *response.status_mut() = StatusCode::OK;
+                        },
+                        Err(error) => {
+                            *response.body_mut() = Body::from(error);
+                        }
+                    }
+                } else if let Some(table) = self.table_from_params(&params).filter(|table| table.open_seating) {
                     let (player_id, new_bot) = {
                         let mut bots = table.bots.lock().unwrap();
-                        let new_bot: Arc<dyn Bot> = match bot_skill {
-                            0 => Arc::new(BotAlwaysCall::new()),
-                            1 => Arc::new(BotEasy::new()),
-                            2 => Arc::new(BotMedium::new()),
-                            _ => Arc::new(BotMedium::new()),
-                        };
-                        let bot = Arc::new(BotInputSource::new(new_bot));
+                        let bot = Arc::new(match table.table.sit_and_go_payouts() {
+                            Some(payouts) => BotInputSource::for_tournament(new_bot, payouts, Box::new(Vec::new)),
+                            None => BotInputSource::new(new_bot),
+                        });
                         bots.push(bot.clone());
                         (format!("bot{}", bots.len()), bot)
                     };
@@ -636,10 +1113,10 @@ impl GameServer {
                                 let input_source = conn;
                                 let player = input_source.server_player();
                                 let channel = &input_source.bet_tx;
-                                if let Some(ServerActionRequest::Bet{call_amount, min_bet}) = player.action_requested.clone() {
+                                if let Some(ServerActionRequest::Bet{call_amount, min_bet, max_bet, ..}) = player.action_requested.clone() {
                                     match resp {
                                         BetResp::Bet(bet) => {
-                                            if player.viewstate.as_ref().map(|s| s.valid_bet(min_bet, call_amount, bet, s.role).is_ok()).unwrap_or(false) {
+                                            if player.viewstate.as_ref().map(|s| s.valid_bet(min_bet, max_bet, call_amount, bet, s.role).is_ok()).unwrap_or(false) {
                                                 channel.send(Some(resp));
                                                 *response.status_mut() = StatusCode::OK;
                                             }
@@ -655,6 +1132,94 @@ impl GameServer {
                     }
                 }
             },
+            (&Method::POST, "/sit_in") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
+                        if let Some(_) = self.get_player(&table, player_id.clone()) {
+                            if table.table.sit_in(&player_id).is_ok() {
+                                *response.status_mut() = StatusCode::OK;
+                            }
+                        }
+                    }
+                }
+            },
+            (&Method::POST, "/sit_out") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
+                        if let Some(_) = self.get_player(&table, player_id.clone()) {
+                            if table.table.sit_out(&player_id).is_ok() {
+                                *response.status_mut() = StatusCode::OK;
+                            }
+                        }
+                    }
+                }
+            },
+            (&Method::POST, "/leave") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
+                        if let Ok(chips) = table.table.leave(&player_id) {
+                            // They join again through /game
+                            table.players.lock().unwrap().remove(&player_id);
+                            *response.body_mut() = Body::from(serde_json::to_vec(&chips).unwrap());
+                            *response.status_mut() = StatusCode::OK;
+                        }
+                    }
+                }
+            },
+            (&Method::POST, "/rebuy") |
+            (&Method::POST, "/add_on") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
+                        if let Some(_) = self.get_player(&table, player_id.clone()) {
+                            let result = if req.uri().path() == "/rebuy" {
+                                table.table.rebuy(&player_id)
+                            } else {
+                                table.table.add_on(&player_id)
+                            };
+                            match result {
+                                Ok(chips) => {
+                                    *response.body_mut() = Body::from(serde_json::to_vec(&chips).unwrap());
+                                    *response.status_mut() = StatusCode::OK;
+                                },
+                                Err(error) => {
+                                    *response.body_mut() = Body::from(format!("{:?}", error));
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            (&Method::GET, "/ledger") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    *response.body_mut() = Body::from(serde_json::to_vec(&table.table.ledger()).unwrap());
+                    *response.status_mut() = StatusCode::OK;
+                }
+            },
+            (&Method::GET, "/rake") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    *response.body_mut() = Body::from(serde_json::to_vec(&table.table.rake_ledger()).unwrap());
+                    *response.status_mut() = StatusCode::OK;
+                }
+            },
+            (&Method::GET, "/settlement") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    match table.table.settlement() {
+                        Ok(settlement) => {
+                            if params.get("format").map(|f| f == "csv").unwrap_or(false) {
+                                *response.body_mut() = Body::from(settlement.to_csv());
+                                response.headers_mut().insert("Content-Type", HeaderValue::from_static("text/csv"));
+                                response.headers_mut().insert("Content-Disposition", HeaderValue::from_static("attachment; filename=\"settlement.csv\""));
+                            } else {
+                                *response.body_mut() = Body::from(serde_json::to_vec(&settlement).unwrap());
+                            }
+                            *response.status_mut() = StatusCode::OK;
+                        },
+                        Err(error) => {
+                            *response.body_mut() = Body::from(error);
+                        }
+                    }
+                }
+            },
             (&Method::POST, "/dealers_choice") => {
                 if let Some(table) = self.table_from_params(&params) {
                     if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
@@ -673,12 +1238,86 @@ impl GameServer {
                     }
                 }
             },
+            (&Method::POST, "/straddle") |
+            (&Method::POST, "/run_it") |
+            (&Method::POST, "/show_or_muck") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
+                        if let Some(player) = self.get_player(&table, player_id) {
+                            let path = req.uri().path().to_string();
+                            if let Ok(resp) = serde_json::from_slice::<bool>(&hyper::body::to_bytes(req.into_body()).await.unwrap()) {
+                                match (path.as_str(), player.server_player().action_requested) {
+                                    ("/straddle", Some(ServerActionRequest::Straddle{..})) => {
+                                        player.straddle_tx.send(Some(resp));
+                                        *response.status_mut() = StatusCode::OK;
+                                    },
+                                    ("/run_it", Some(ServerActionRequest::RunIt{..})) => {
+                                        player.run_it_tx.send(Some(resp));
+                                        *response.status_mut() = StatusCode::OK;
+                                    },
+                                    ("/show_or_muck", Some(ServerActionRequest::ShowOrMuck{..})) => {
+                                        player.show_or_muck_tx.send(Some(resp));
+                                        *response.status_mut() = StatusCode::OK;
+                                    },
+                                    _ => {
+                                        println!("No action requested");
+                                    }
+                                }
+                            } else {
+                                println!("Failed to parse request");
+                            }
+                        } else {
+                            println!("Failed to find player");
+                        }
+                    } else {
+                        println!("No player id in request");
+                    }
+                }
+            },
+            (&Method::POST, "/show_uncontested") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
+                        if let Some(player) = self.get_player(&table, player_id) {
+                            if let Ok(resp) = serde_json::from_slice::<ShowResp>(&hyper::body::to_bytes(req.into_body()).await.unwrap()) {
+                                if let Some(ServerActionRequest::ShowUncontested{..}) = player.server_player().action_requested {
+                                    player.show_uncontested_tx.send(Some(resp));
+                                    *response.status_mut() = StatusCode::OK;
+                                } else {
+                                    println!("No action requested");
+                                }
+                            } else {
+                                println!("Failed to parse request");
+                            }
+                        } else {
+                            println!("Failed to find player");
+                        }
+                    } else {
+                        println!("No player id in request");
+                    }
+                }
+            },
+            (&Method::POST, "/vote_bomb_pot") => {
+                if let Some(table) = self.table_from_params(&params) {
+                    if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
+                        if let Some(_) = self.get_player(&table, player_id.clone()) {
+                            match table.table.vote_bomb_pot(&player_id) {
+                                Ok(()) => {
+                                    *response.status_mut() = StatusCode::OK;
+                                },
+                                Err(error) => {
+                                    *response.body_mut() = Body::from(format!("{:?}", error));
+                                }
+                            }
+                        }
+                    }
+                }
+            },
             (&Method::POST, "/replace") => {
                 if let Some(table) = self.table_from_params(&params) {
                     if let Some(player_id) = table.get_player_id(game_server_id, param_id) {
                         if let Some(player) = self.get_player(&table, player_id) {
                             if let Ok(resp) = serde_json::from_slice::<ReplaceResp>(&hyper::body::to_bytes(req.into_body()).await.unwrap()) {
-                                if let Some(ServerActionRequest::Replace{max_can_replace}) = player.server_player().action_requested.clone() {
+                                if let Some(ServerActionRequest::Replace{max_can_replace, ..}) = player.server_player().action_requested.clone() {
                                     if resp.len() <= max_can_replace {
                                         player.replace_tx.send(Some(resp));
                                         *response.status_mut() = StatusCode::OK;
@@ -718,14 +1357,24 @@ impl GameServer {
 
 impl AnteRuleDesc {
     pub fn starting_rules(&self) -> TableRules {
-        let starting_bet = (*self.rule_fn())(0, std::time::Duration::from_secs(0));
+        let starting_bet = match &self.schedule {
+            Some(schedule) => schedule.first_level().ante_rule(),
+            None => (*self.rule_fn())(0, std::time::Duration::from_secs(0)),
+        };
         TableRules {
             ante: starting_bet.clone(),
             ante_name: (if self.blinds {"Ante"} else {"Blind"}).to_string(),
             min_bet: match starting_bet {
                 AnteRule::Ante(ante) => ante,
-                AnteRule::Blinds(blinds) => blinds.iter().map(|b| b.amount).max().unwrap(),
-            }
+                AnteRule::BringIn{bring_in, ..} => bring_in * 2,
+                AnteRule::Blinds(blinds) | AnteRule::BlindsAnte{blinds, ..} => blinds.iter().map(|b| b.amount).max().unwrap(),
+            },
+            betting: BettingStructure::NoLimit,
+            rake: None,
+            straddle: None,
+            bomb_pot: None,
+            run_it: None,
+            all_in_pause_ms: 0,
         }
     }
 
@@ -735,6 +1384,7 @@ impl AnteRuleDesc {
         let starting_chips: Chips = self.starting_value;
         let change: AnteRuleChangeDesc = self.change;
         let blinds = self.blinds;
+        let bring_in = self.bring_in;
 
         Box::new(move |round, server_uptime| {
             println!("In the rule fn");
@@ -756,6 +1406,11 @@ impl AnteRuleDesc {
                 }, Blind{
                     amount: low_blind * 2,
                 }])
+            } else if bring_in {
+                AnteRule::BringIn {
+                    ante: low_blind,
+                    bring_in: low_blind * 2,
+                }
             } else {
                 AnteRule::Ante(low_blind)
             }
@@ -810,3 +1465,188 @@ fn load_private_key(filename: &str) -> std::io::Result<rustls::PrivateKey> {
         Err(error(format!("expected a single private key, got cert")))
     }
 }
+
+mod test {
+    use crate::server::*;
+
+    fn timed_table_config() -> TableConfig {
+        TableConfig {
+            max_players: 2,
+            starting_chips: 100,
+            variant_selector: PokerVariantSelector::Rotation(PokerVariants{descs: vec![PokerVariantDesc{name: "Texas Hold 'Em".to_string(), special_cards: Vec::new()}]}),
+            timer: ActionTimer{action_secs: Some(10), time_bank_secs: 0},
+            rebuys: RebuyRules::default(),
+            sit_and_go: None,
+        }
+    }
+
+    fn table_with_config(table_config: TableConfig) -> Table {
+        let ante_rule = AnteRuleDesc {
+            starting_value: 1,
+            blinds: true,
+            bring_in: false,
+            change: AnteRuleChangeDesc::Constant,
+            schedule: None,
+        };
+        let static_config = StaticConfig {
+            serve_address: String::new(),
+            serve_port: 0,
+            tls: false,
+            cert_path: String::new(),
+            key_path: String::new(),
+            ms_between_rounds: 0,
+            variants_dir: None,
+        };
+        Table::with_static_config(table_config, ante_rule.starting_rules(), ante_rule.rule_fn(), static_config)
+    }
+
+    fn timed_table() -> Table {
+        table_with_config(timed_table_config())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_time_bank() {
+        let input = Arc::new(GameServerPlayerInputSource::new(ActionTimer{action_secs: Some(10), time_bank_secs: 5}));
+        let answer = input.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(12)).await;
+            answer.bet_tx.send(Some(BetResp::Bet(4)));
+        });
+        assert_eq!(input.bet(4, 2, 100).await, BetResp::Bet(4));
+        // Two seconds over the action timer came out of the time bank
+        assert_eq!(*input.time_bank.lock().unwrap(), std::time::Duration::from_secs(3));
+        assert!(!input.sitting_out());
+
+        // Nobody answers, so the player checks once both run out
+        let start = tokio::time::Instant::now();
+        assert_eq!(input.bet(0, 2, 100).await, BetResp::Bet(0));
+        assert_eq!(start.elapsed(), std::time::Duration::from_secs(13));
+        assert_eq!(*input.time_bank.lock().unwrap(), std::time::Duration::from_secs(0));
+        assert!(input.sitting_out());
+        // and is checked or folded for straight away after that
+        assert_eq!(input.bet(4, 2, 100).await, BetResp::Fold);
+        assert_eq!(start.elapsed(), std::time::Duration::from_secs(13));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_straddle_unanswered() {
+        let input = GameServerPlayerInputSource::new(ActionTimer{action_secs: Some(10), time_bank_secs: 5});
+        let start = tokio::time::Instant::now();
+        assert!(!input.straddle(4).await);
+        // Declined without touching the time bank or sitting the player out
+        assert_eq!(start.elapsed(), std::time::Duration::from_secs(ASK_SECS));
+        assert_eq!(*input.time_bank.lock().unwrap(), std::time::Duration::from_secs(5));
+        assert!(!input.sitting_out());
+        assert_eq!(input.server_player().action_requested, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_it_answered() {
+        let input = Arc::new(GameServerPlayerInputSource::new(ActionTimer{action_secs: Some(10), time_bank_secs: 5}));
+        let answer = input.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            answer.run_it_tx.send(Some(true));
+        });
+        assert!(input.run_it(2).await);
+        assert!(!input.run_it(2).await);
+        assert_eq!(*input.time_bank.lock().unwrap(), std::time::Duration::from_secs(5));
+        assert!(!input.sitting_out());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sit_out_inactive() {
+        let table = timed_table();
+        let inputs: Vec<Arc<GameServerPlayerInputSource>> = (0..2).map(|_| Arc::new(GameServerPlayerInputSource::new(table.config().timer))).collect();
+        for (player_id, input) in inputs.iter().enumerate() {
+            assert!(table.join(player_id.to_string(), input.clone()).is_ok());
+        }
+        table.start();
+        // The button acts first heads up, runs out of time and folds their small blind
+        assert!(table.next_round().await);
+        assert!(inputs[0].sitting_out());
+        assert!(!inputs[1].sitting_out());
+        assert_eq!(table.chips(), vec![("0".to_string(), 99), ("1".to_string(), 101)].into_iter().collect());
+        // They are sat out before the next hand, which leaves too few players to deal it
+        assert!(table.next_round().await);
+        assert_eq!(table.current_viewstate().sitting_out, vec!["0".to_string()]);
+        let events: Vec<TableEvent> = table.logs(None, 0).into_iter().flat_map(|update| update.log).filter_map(|diff| match diff {
+            TableViewDiff::TableDiff(event) => Some(event),
+            TableViewDiff::GameDiff(_) => None,
+        }).collect();
+        assert!(events.contains(&TableEvent::PlayerSatOut{player_id: "0".to_string()}));
+    }
+
+    struct AlwaysCall;
+
+    #[async_trait]
+    impl PlayerInputSource for AlwaysCall {
+        async fn bet(&self, call_amount: Chips, _min_bet: Chips, _max_bet: Chips) -> BetResp {
+            BetResp::Bet(call_amount)
+        }
+
+        async fn replace(&self, _max_can_replace: usize) -> ReplaceResp {
+            Vec::new()
+        }
+
+        async fn dealers_choice(&self, _variants: Vec<PokerVariantDesc>) -> DealersChoiceResp {
+            DealersChoiceResp::default()
+        }
+
+        fn update(&self, _update: PokerViewUpdate) {}
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sit_and_go_blinds_off() {
+        let table = table_with_config(TableConfig {
+            starting_chips: 10,
+            sit_and_go: Some(PrizeStructure{percentages: vec![100]}),
+            ..timed_table_config()
+        });
+        let timed_out = Arc::new(GameServerPlayerInputSource::new(table.config().timer));
+        assert!(table.join("0".to_string(), timed_out.clone()).is_ok());
+        assert!(table.join("1".to_string(), Arc::new(AlwaysCall)).is_ok());
+        let events = || -> Vec<TableEvent> {
+            table.logs(None, 0).into_iter().flat_map(|update| update.log).filter_map(|diff| match diff {
+                TableViewDiff::TableDiff(event) => Some(event),
+                TableViewDiff::GameDiff(_) => None,
+            }).collect()
+        };
+        // Whoever acts first for "0" runs out of time, and they are sat out from then on
+        let mut hands = 0;
+        while !timed_out.sitting_out() {
+            assert!(table.next_round().await);
+            hands += 1;
+        }
+        // but they are still dealt in and blind off until one player has every chip
+        while !events().iter().any(|event| matches!(event, TableEvent::TournamentFinished{..})) {
+            assert!(hands < 1000);
+            table.next_round().await;
+            hands += 1;
+        }
+        assert!(events().contains(&TableEvent::PlayerSatOut{player_id: "0".to_string()}));
+        assert_eq!(table.chips().values().sum::<Chips>(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_leave_and_rejoin() {
+        let server = GameServer {
+            tables: Mutex::new(BTreeMap::new()),
+            tournaments: Mutex::new(BTreeMap::new()),
+            id_counter: IdCounter::new(),
+            auth: Mutex::new(RandomTokenAuthMap::new(512)),
+            static_files: StaticFiles::from_dir_path("ts/static"),
+        };
+        let (table_id, table) = server.add_table(timed_table(), true);
+        let request = |method: Method, path: &str, query: &str| Request::builder().method(method).uri(format!("{}?{}table_id={}&player=a", path, query, table_id)).body(Body::empty()).unwrap();
+        assert_eq!(server.serve(request(Method::GET, "/game", "")).await.unwrap().status(), StatusCode::OK);
+        assert!(table.table.chips().contains_key("a"));
+        assert_eq!(server.serve(request(Method::POST, "/leave", "")).await.unwrap().status(), StatusCode::OK);
+        assert!(!table.table.chips().contains_key("a"));
+        // Polling doesn't sit them back down, but joining does
+        assert_eq!(server.serve(request(Method::GET, "/gamediff", "start_from=0&")).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert!(!table.table.chips().contains_key("a"));
+        assert_eq!(server.serve(request(Method::GET, "/game", "")).await.unwrap().status(), StatusCode::OK);
+        assert!(table.table.chips().contains_key("a"));
+    }
+}
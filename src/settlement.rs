@@ -0,0 +1,262 @@
+use crate::game::*;
+use crate::table::*;
+
+use ts_rs::{TS, export};
+use serde::{Serialize, Deserialize};
+
+use std::collections::{HashMap, BTreeMap};
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct PlayerBalance {
+    pub player_id: PlayerId,
+    pub bought_in: Chips,
+    // Chips at the table, plus any the player left with
+    pub cashed_out: Chips,
+    pub net: Chips,
+    // Part of cashed_out given back from a pot that carried over when the table stopped
+    #[serde(default)]
+    pub refunded: Chips,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct Transfer {
+    pub from: PlayerId,
+    pub to: PlayerId,
+    pub chips: Chips,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct Settlement {
+    pub balances: Vec<PlayerBalance>,
+    pub transfers: Vec<Transfer>,
+}
+
+// Settles up from every buy-in in the ledger and the chips each player ended with
+pub fn settle(ledger: &[LedgerEntry], final_chips: &HashMap<PlayerId, Chips>, refunds: &HashMap<PlayerId, Chips>) -> Settlement {
+    let mut totals: BTreeMap<PlayerId, (Chips, Chips)> = BTreeMap::new();
+    for entry in ledger {
+        totals.entry(entry.player_id.clone()).or_default().0 += entry.chips;
+    }
+    for (player_id, &chips) in final_chips {
+        totals.entry(player_id.clone()).or_default().1 += chips;
+    }
+    let balances: Vec<PlayerBalance> = totals.into_iter().map(|(player_id, (bought_in, cashed_out))| PlayerBalance {
+        refunded: refunds.get(&player_id).copied().unwrap_or(0),
+        player_id,
+        bought_in,
+        cashed_out,
+        net: cashed_out - bought_in,
+    }).collect();
+    let transfers = min_transfers(&balances);
+    Settlement {
+        balances,
+        transfers,
+    }
+}
+
+// Past this many players with a balance, the search for the fewest transfers takes too long
+const EXACT_MAX_PLAYERS: usize = 16;
+
+// Splits the players into as many groups as possible that square up among themselves. A group of
+// n players needs n-1 transfers, so this gives the fewest transfers overall
+fn min_transfers(balances: &[PlayerBalance]) -> Vec<Transfer> {
+    let players: Vec<(PlayerId, Chips)> = balances.iter().filter(|b| b.net != 0).map(|b| (b.player_id.clone(), b.net)).collect();
+    if players.len() > EXACT_MAX_PLAYERS {
+        return greedy_transfers(&players);
+    }
+    zero_sum_groups(&players.iter().map(|(_, net)| *net).collect::<Vec<Chips>>()).into_iter().flat_map(|group| {
+        greedy_transfers(&group.into_iter().map(|idx| players[idx].clone()).collect::<Vec<(PlayerId, Chips)>>())
+    }).collect()
+}
+
+// Partitions nets into the most groups that each sum to zero. If the nets don't sum to zero,
+// e.g. because of rake, the players left over make up one last group
+fn zero_sum_groups(nets: &[Chips]) -> Vec<Vec<usize>> {
+    let full = (1usize << nets.len()) - 1;
+    let mut sums = vec![0; full + 1];
+    // The most zero-sum groups some ordering of the set splits into
+    let mut groups = vec![0; full + 1];
+    for set in 1..=full {
+        let lowest = set.trailing_zeros() as usize;
+        sums[set] = sums[set & (set - 1)] + nets[lowest];
+        let best = (0..nets.len()).filter(|i| set & (1 << i) != 0).map(|i| groups[set & !(1 << i)]).max().unwrap();
+        groups[set] = best + if sums[set] == 0 {1} else {0};
+    }
+    // Take players off the end of the best ordering, then cut it wherever the running total is zero
+    let mut order = Vec::new();
+    let mut set = full;
+    while set != 0 {
+        let want = groups[set] - if sums[set] == 0 {1} else {0};
+        let last = (0..nets.len()).find(|&i| set & (1 << i) != 0 && groups[set & !(1 << i)] == want).unwrap();
+        order.push(last);
+        set &= !(1 << last);
+    }
+    order.reverse();
+    let mut retval = Vec::new();
+    let mut group = Vec::new();
+    let mut total = 0;
+    for idx in order {
+        group.push(idx);
+        total += nets[idx];
+        if total == 0 {
+            retval.push(std::mem::take(&mut group));
+        }
+    }
+    if !group.is_empty() {
+        retval.push(group);
+    }
+    retval
+}
+
+// Repeatedly has the biggest loser pay the biggest winner. Each transfer squares up at least
+// one player, so there are fewer transfers than players with a balance
+fn greedy_transfers(players: &[(PlayerId, Chips)]) -> Vec<Transfer> {
+    let mut owes: Vec<(PlayerId, Chips)> = players.iter().filter(|(_, net)| *net < 0).map(|(p, net)| (p.clone(), -net)).collect();
+    let mut owed: Vec<(PlayerId, Chips)> = players.iter().filter(|(_, net)| *net > 0).cloned().collect();
+    let mut retval = Vec::new();
+    loop {
+        // Ties go to the first player by id so the result is deterministic
+        owes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        owed.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let (from, to) = match (owes.first_mut(), owed.first_mut()) {
+            (Some(from), Some(to)) => (from, to),
+            _ => break,
+        };
+        let chips = std::cmp::min(from.1, to.1);
+        retval.push(Transfer {
+            from: from.0.clone(),
+            to: to.0.clone(),
+            chips,
+        });
+        from.1 -= chips;
+        to.1 -= chips;
+        owes.retain(|(_, c)| *c > 0);
+        owed.retain(|(_, c)| *c > 0);
+    }
+    retval
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl Settlement {
+    pub fn to_csv(&self) -> String {
+        let mut retval = "player,bought_in,cashed_out,net,refunded\n".to_string();
+        for PlayerBalance{player_id, bought_in, cashed_out, net, refunded} in &self.balances {
+            retval += &format!("{},{},{},{},{}\n", csv_field(player_id), bought_in, cashed_out, net, refunded);
+        }
+        retval += "\nfrom,to,chips\n";
+        for Transfer{from, to, chips} in &self.transfers {
+            retval += &format!("{},{},{}\n", csv_field(from), csv_field(to), chips);
+        }
+        retval
+    }
+}
+
+mod test {
+    use crate::settlement::*;
+
+    fn buy_in(player_id: &str, chips: Chips) -> LedgerEntry {
+        LedgerEntry {
+            player_id: player_id.to_string(),
+            kind: BuyInKind::BuyIn,
+            chips,
+            hand: 0,
+        }
+    }
+
+    #[test]
+    fn test_settle() {
+        let ledger = vec![
+            buy_in("a", 100),
+            buy_in("b", 100),
+            buy_in("c", 100),
+            buy_in("d", 100),
+            LedgerEntry{kind: BuyInKind::Rebuy, hand: 10, ..buy_in("d", 100)},
+        ];
+        let final_chips: HashMap<PlayerId, Chips> = vec![
+            ("a".to_string(), 250),
+            ("b".to_string(), 150),
+            ("c".to_string(), 100),
+            ("d".to_string(), 0),
+        ].into_iter().collect();
+        let settlement = settle(&ledger, &final_chips, &HashMap::new());
+        assert_eq!(settlement.balances.iter().map(|b| b.net).collect::<Vec<Chips>>(), vec![150, 50, 0, -200]);
+        assert_eq!(settlement.transfers, vec![
+            Transfer{from: "d".to_string(), to: "a".to_string(), chips: 150},
+            Transfer{from: "d".to_string(), to: "b".to_string(), chips: 50},
+        ]);
+    }
+
+    #[test]
+    fn test_settle_many_losers() {
+        let ledger: Vec<LedgerEntry> = ["a", "b", "c", "d", "e"].iter().map(|p| buy_in(p, 100)).collect();
+        let final_chips: HashMap<PlayerId, Chips> = vec![
+            ("a".to_string(), 0),
+            ("b".to_string(), 40),
+            ("c".to_string(), 70),
+            ("d".to_string(), 190),
+            ("e".to_string(), 200),
+        ].into_iter().collect();
+        let settlement = settle(&ledger, &final_chips, &HashMap::new());
+        assert!(settlement.transfers.len() < 5);
+        let mut net: HashMap<PlayerId, Chips> = settlement.balances.iter().map(|b| (b.player_id.clone(), b.net)).collect();
+        for Transfer{from, to, chips} in &settlement.transfers {
+            *net.get_mut(from).unwrap() += chips;
+            *net.get_mut(to).unwrap() -= chips;
+        }
+        assert!(net.values().all(|&n| n == 0));
+    }
+
+    #[test]
+    fn test_settle_fewest_transfers() {
+        // Biggest loser pays biggest winner takes four transfers here, but three will do
+        let ledger: Vec<LedgerEntry> = ["a", "b", "c", "d", "e"].iter().map(|p| buy_in(p, 10)).collect();
+        let final_chips: HashMap<PlayerId, Chips> = vec![
+            ("a".to_string(), 3),
+            ("b".to_string(), 5),
+            ("c".to_string(), 7),
+            ("d".to_string(), 18),
+            ("e".to_string(), 17),
+        ].into_iter().collect();
+        let mut transfers = settle(&ledger, &final_chips, &HashMap::new()).transfers;
+        transfers.sort_by(|a, b| a.from.cmp(&b.from));
+        assert_eq!(transfers, vec![
+            Transfer{from: "a".to_string(), to: "e".to_string(), chips: 7},
+            Transfer{from: "b".to_string(), to: "d".to_string(), chips: 5},
+            Transfer{from: "c".to_string(), to: "d".to_string(), chips: 3},
+        ]);
+    }
+
+    #[test]
+    fn test_zero_sum_groups() {
+        let sorted_groups = |nets: &[Chips]| {
+            let mut groups = zero_sum_groups(nets);
+            for group in &mut groups {
+                group.sort();
+            }
+            groups.sort();
+            groups
+        };
+        assert_eq!(sorted_groups(&[]), Vec::<Vec<usize>>::new());
+        assert_eq!(sorted_groups(&[5, -5]), vec![vec![0, 1]]);
+        assert_eq!(sorted_groups(&[-7, -5, -3, 8, 7]), vec![vec![0, 4], vec![1, 2, 3]]);
+        // Raked chips leave one group short
+        assert_eq!(sorted_groups(&[-4, 4, -3, 2]), vec![vec![0, 1], vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_settlement_csv() {
+        let refunds = vec![("c".to_string(), 2)].into_iter().collect();
+        let settlement = settle(&[buy_in("a,b", 10), buy_in("c", 10)], &vec![("a,b".to_string(), 18), ("c".to_string(), 2)].into_iter().collect(), &refunds);
+        assert_eq!(settlement.to_csv(), "player,bought_in,cashed_out,net,refunded\n\"a,b\",10,18,8,0\nc,10,2,-8,2\n\nfrom,to,chips\nc,\"a,b\",8\n");
+    }
+}
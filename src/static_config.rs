@@ -9,6 +9,8 @@ pub struct StaticConfig {
     pub cert_path: String,
     pub key_path: String,
     pub ms_between_rounds: u64,
+    #[serde(default)]
+    pub variants_dir: Option<String>,
 }
 
 pub fn read_static_config() -> StaticConfig {
@@ -5,13 +5,17 @@ use crate::game::*;
 use crate::gamestate::*;
 use crate::viewstate::*;
 use crate::fold_channel;
+use crate::settlement::*;
+use crate::blind_schedule::*;
+use crate::tournament::*;
 
 use ts_rs::{TS, export};
 
 use tokio::sync::watch;
 use serde::{Serialize, Deserialize};
+use async_trait::async_trait;
 
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, HashSet, BTreeMap};
 
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -27,7 +31,18 @@ pub struct Blind {
 #[derive(TS, Serialize, Deserialize)]
 pub enum AnteRule {
     Ante(Chips),
-    Blinds(Vec<Blind>)
+    Blinds(Vec<Blind>),
+    // Blinds plus an ante. With big_blind_ante, the big blind pays a single ante for the whole table
+    BlindsAnte {
+        blinds: Vec<Blind>,
+        ante: Chips,
+        big_blind_ante: bool,
+    },
+    // Antes, then the first player in a bring-in betting round is forced to bet bring_in
+    BringIn {
+        ante: Chips,
+        bring_in: Chips,
+    },
 }
 
 pub type AnteRuleFn = dyn Send + (Fn(usize, Duration) -> AnteRule);
@@ -37,7 +52,142 @@ pub type AnteRuleFn = dyn Send + (Fn(usize, Duration) -> AnteRule);
 pub struct TableRules {
     pub ante: AnteRule,
     pub ante_name: String,
-    pub min_bet: Chips
+    pub min_bet: Chips,
+    #[serde(default)]
+    pub betting: BettingStructure,
+    #[serde(default)]
+    pub rake: Option<Rake>,
+    #[serde(default)]
+    pub straddle: Option<StraddleRule>,
+    #[serde(default)]
+    pub bomb_pot: Option<BombPotRule>,
+    // Boards to offer when everyone is all in before the last community cards
+    #[serde(default)]
+    pub run_it: Option<usize>,
+    // Pause between streets once the hands are shown because everyone is all in
+    #[serde(default)]
+    pub all_in_pause_ms: u64,
+}
+
+// Who may post a straddle of twice the big blind before the cards are dealt
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(TS, Serialize, Deserialize)]
+#[serde(tag = "kind", content="data")]
+pub enum StraddleRule {
+    UnderTheGun,
+    Button,
+}
+
+// In a bomb pot everyone antes and the first betting round is skipped
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(TS, Serialize, Deserialize)]
+pub struct BombPotRule {
+    pub ante: Chips,
+    // Every this many hands, if set
+    pub every_hands: Option<usize>,
+    // Whether a majority of the players at the table can call for one
+    pub by_vote: bool,
+}
+
+impl BombPotRule {
+    pub fn is_valid(&self) -> bool {
+        self.ante > 0 && self.every_hands != Some(0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(TS, Serialize, Deserialize)]
+pub struct Rake {
+    // Percent of each pot, rounded down
+    pub percent: u32,
+    // The most taken from any one pot
+    pub cap: Option<Chips>,
+    // Hands that end in the first betting round aren't raked
+    pub no_flop_no_drop: bool,
+}
+
+impl Rake {
+    pub fn is_valid(&self) -> bool {
+        self.percent <= 100 && self.cap.map(|cap| cap >= 0).unwrap_or(true)
+    }
+
+    pub fn amount(&self, pot: Chips) -> Chips {
+        let rake = pot * self.percent as Chips / 100;
+        self.cap.map(|cap| std::cmp::min(rake, cap)).unwrap_or(rake)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct RakeEntry {
+    // Number of hands played before this one
+    pub hand: usize,
+    pub chips: Chips,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(TS, Serialize, Deserialize)]
+#[serde(tag = "kind", content="data")]
+pub enum BettingStructure {
+    NoLimit,
+    // Raises are capped at the size of the pot after calling
+    PotLimit,
+    // Every bet and raise is small_bet in the early streets and big_bet in the later ones.
+    // raise_cap counts the opening bet (or blind) too
+    FixedLimit {
+        small_bet: Chips,
+        big_bet: Chips,
+        raise_cap: usize,
+    },
+}
+
+impl Default for BettingStructure {
+    fn default() -> BettingStructure {
+        BettingStructure::NoLimit
+    }
+}
+
+impl BettingStructure {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            BettingStructure::FixedLimit{small_bet, big_bet, raise_cap} => *small_bet > 0 && *big_bet >= *small_bet && *raise_cap > 0,
+            _ => true
+        }
+    }
+
+    // Returns the (min, max) total a player may bet this round if they raise.
+    // last_bet is the amount to call, and pot_after_call includes the player's call
+    pub fn raise_limits(&self, min_bet: Chips, late_street: bool, last_bet: Chips, last_raise: Chips, raises: usize, pot_after_call: Chips) -> (Chips, Chips) {
+        use BettingStructure::*;
+        let min_raise = last_bet + std::cmp::max(last_raise, min_bet);
+        match self {
+            NoLimit => (min_raise, Chips::MAX),
+            PotLimit => (min_raise, std::cmp::max(min_raise, last_bet + pot_after_call)),
+            FixedLimit{small_bet, big_bet, raise_cap} => {
+                let size = if late_street {*big_bet} else {*small_bet};
+                if raises >= *raise_cap {
+                    (last_bet, last_bet)
+                } else if raises == 0 && last_bet < size {
+                    // Completing a bring-in
+                    (size, size)
+                } else {
+                    (last_bet + size, last_bet + size)
+                }
+            }
+        }
+    }
+
+    // Keeps fixed limit bet sizes in proportion to the min bet as the blinds go up
+    pub fn rescaled(&self, old_min_bet: Chips, new_min_bet: Chips) -> BettingStructure {
+        match self {
+            BettingStructure::FixedLimit{small_bet, big_bet, raise_cap} if old_min_bet > 0 => BettingStructure::FixedLimit {
+                small_bet: std::cmp::max(1, small_bet * new_min_bet / old_min_bet),
+                big_bet: std::cmp::max(1, big_bet * new_min_bet / old_min_bet),
+                raise_cap: *raise_cap,
+            },
+            other => *other
+        }
+    }
 }
 
 #[derive(Eq, Copy, Clone, PartialEq, Hash, Debug, Ord, PartialOrd, Serialize, Deserialize)]
@@ -76,6 +226,29 @@ struct TableState {
     start_time: std::time::Instant,
     past_time: Duration,
     ante_rule: Box<AnteRuleFn>,
+    // Replaces ante_rule when the table has explicit levels
+    // Shared between tables in a tournament
+    schedule: Option<Arc<Mutex<ScheduleClock>>>,
+    schedule_idx: usize,
+    // Players sitting out. Their hands are checked or folded for them
+    away: Arc<Mutex<HashSet<PlayerId>>>,
+    // Players who left mid-hand. They fold, and are removed once the hand is settled
+    leaving: Arc<Mutex<HashSet<PlayerId>>>,
+    in_hand: bool,
+    ledger: Vec<LedgerEntry>,
+    // Chips taken by players who left the table
+    cashed_out: HashMap<PlayerId, Chips>,
+    // Sit-and-go finishing places, best first
+    standings: Vec<Standing>,
+    rake: Vec<RakeEntry>,
+    // Players asking for a bomb pot next hand
+    bomb_pot_votes: HashSet<PlayerId>,
+    // Chips each player put into passed out hands, dead in the next pot
+    carried_pot: HashMap<PlayerId, Chips>,
+    // Carried chips given back because the table stopped before anyone won them
+    refunds: HashMap<PlayerId, Chips>,
+    // Seated by a tournament, whose chips stay in play until the player busts
+    in_tournament: bool,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -108,6 +281,99 @@ pub struct TableConfig {
     pub max_players: usize,
     pub starting_chips: Chips,
     pub variant_selector: PokerVariantSelector,
+    #[serde(default)]
+    pub timer: ActionTimer,
+    #[serde(default)]
+    pub rebuys: RebuyRules,
+    // Starts once every seat is taken and plays until one player has all the chips, paying
+    // out the buy-ins by finishing place
+    #[serde(default)]
+    pub sit_and_go: Option<PrizeStructure>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct ActionTimer {
+    // Seconds each player has to act, or None to wait forever
+    pub action_secs: Option<u64>,
+    // Extra seconds each player can use up over the whole game once their action timer runs out
+    pub time_bank_secs: u64,
+}
+
+impl Default for ActionTimer {
+    fn default() -> ActionTimer {
+        ActionTimer {
+            action_secs: None,
+            time_bank_secs: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct RebuyRules {
+    // Busted players may buy back in between hands
+    pub allowed: bool,
+    // None for unlimited rebuys
+    pub max_rebuys: Option<usize>,
+    pub amount: Chips,
+    #[serde(default)]
+    pub add_on: Option<AddOn>,
+}
+
+impl Default for RebuyRules {
+    fn default() -> RebuyRules {
+        RebuyRules {
+            allowed: false,
+            max_rebuys: None,
+            amount: 0,
+            add_on: None,
+        }
+    }
+}
+
+impl RebuyRules {
+    pub fn is_valid(&self) -> bool {
+        (!self.allowed || self.amount > 0)
+            && self.add_on.map(|a| a.amount > 0).unwrap_or(true)
+    }
+}
+
+// A single extra buy-in every player may take between hands until `until_hand` hands have been played
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct AddOn {
+    pub amount: Chips,
+    pub until_hand: usize,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+#[serde(tag = "kind", content="data")]
+pub enum BuyInKind {
+    BuyIn,
+    Rebuy,
+    AddOn,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct LedgerEntry {
+    pub player_id: PlayerId,
+    pub kind: BuyInKind,
+    pub chips: Chips,
+    // Number of hands played before the buy-in
+    pub hand: usize,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum BuyInError {
+    NotJoined,
+    NotAllowed,
+    InHand,
+    NotBusted,
+    LimitReached,
+    WindowClosed,
 }
 
 pub struct Table {
@@ -115,6 +381,8 @@ pub struct Table {
     running_rx: watch::Receiver<bool>,
     config: TableConfig,
     static_config: StaticConfig,
+    // Shuffled for every hand
+    deck: VecDeck,
     state: Mutex<TableState>,
     spectator_tx: fold_channel::Sender<Vec<PokerGlobalViewDiff<PlayerId>>, Vec<PokerGlobalViewDiff<PlayerId>>>,
     pub spectator_rx: fold_channel::Receiver<Vec<PokerGlobalViewDiff<PlayerId>>>,
@@ -124,12 +392,114 @@ pub struct Table {
 
 pub enum JoinError {
     Full,
-    AlreadyJoined
+    AlreadyJoined,
+    Started,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum SeatError {
+    NotJoined,
+    Leaving,
+    InHand,
+    NotAllowed,
+}
+
+// Acts for the player while they are away from the table
+struct SeatedInput {
+    player_id: PlayerId,
+    input: Arc<dyn PlayerInputSource>,
+    away: Arc<Mutex<HashSet<PlayerId>>>,
+    leaving: Arc<Mutex<HashSet<PlayerId>>>,
+    bet_this_round: Mutex<Chips>,
+}
+
+impl SeatedInput {
+    fn is_away(&self) -> bool {
+        self.away.lock().unwrap().contains(&self.player_id) || self.is_leaving()
+    }
+
+    fn is_leaving(&self) -> bool {
+        self.leaving.lock().unwrap().contains(&self.player_id)
+    }
+}
+
+#[async_trait]
+impl PlayerInputSource for SeatedInput {
+    async fn bet(&self, call_amount: Chips, min_bet: Chips, max_bet: Chips) -> BetResp {
+        if self.is_leaving() {
+            return BetResp::Fold;
+        }
+        if self.is_away() {
+            return check_or_fold(call_amount, *self.bet_this_round.lock().unwrap());
+        }
+        let resp = self.input.bet(call_amount, min_bet, max_bet).await;
+        // They may have left while deciding
+        if self.is_leaving() {
+            return BetResp::Fold;
+        }
+        resp
+    }
+
+    async fn replace(&self, max_can_replace: usize) -> ReplaceResp {
+        if self.is_away() {
+            return Vec::new();
+        }
+        self.input.replace(max_can_replace).await
+    }
+
+    async fn dealers_choice(&self, variants: Vec<PokerVariantDesc>) -> DealersChoiceResp {
+        self.input.dealers_choice(variants).await
+    }
+
+    async fn straddle(&self, amount: Chips) -> bool {
+        if self.is_away() {
+            return false;
+        }
+        self.input.straddle(amount).await
+    }
+
+    async fn run_it(&self, times: usize) -> bool {
+        if self.is_away() {
+            return false;
+        }
+        self.input.run_it(times).await
+    }
+
+    async fn show_or_muck(&self) -> bool {
+        if self.is_away() {
+            return false;
+        }
+        self.input.show_or_muck().await
+    }
+
+    async fn show_uncontested(&self, num_cards: usize) -> ShowResp {
+        if self.is_away() {
+            return ShowResp::Muck;
+        }
+        self.input.show_uncontested(num_cards).await
+    }
+
+    fn update(&self, update: PokerViewUpdate) {
+        *self.bet_this_round.lock().unwrap() = update.viewstate.bet_this_round.get(&update.viewstate.role).copied().unwrap_or(0);
+        self.input.update(update);
+    }
+
+    fn sitting_out(&self) -> bool {
+        self.input.sitting_out()
+    }
+
+    fn set_sitting_out(&self, sitting_out: bool) {
+        self.input.set_sitting_out(sitting_out);
+    }
 }
 
 impl TableConfig {
     pub fn is_valid(&self) -> bool {
-        self.variant_selector.is_valid() && self.variant_selector.all().into_iter().all(|v| self.max_players <= variant_max_players(&v, standard_deck().raw.len()))
+        self.variant_selector.is_valid()
+            && self.timer.action_secs != Some(0)
+            && self.rebuys.is_valid()
+            && self.sit_and_go.as_ref().map(|prizes| prizes.is_valid() && !self.rebuys.allowed && self.rebuys.add_on.is_none()).unwrap_or(true)
+            && self.variant_selector.all().into_iter().all(|v| self.max_players <= variant_max_players(&v, standard_deck().raw.len()))
     }
 }
 
@@ -190,7 +560,136 @@ impl TableState {
         retval
     }
 
-    fn next_round_roles(&self) -> HashMap<PlayerRole, (Seat, PlayerId)> {
+    fn is_away(&self, player_id: &PlayerId) -> bool {
+        self.away.lock().unwrap().contains(player_id)
+    }
+
+    fn is_leaving(&self, player_id: &PlayerId) -> bool {
+        self.leaving.lock().unwrap().contains(player_id)
+    }
+
+    // Players who stopped acting on their own sit out from the next hand
+    fn sit_out_inactive(&mut self) {
+        let mut inactive: Vec<PlayerId> = self.players.values().filter(|p| p.input.sitting_out() && !self.is_away(&p.player_id)).map(|p| p.player_id.clone()).collect();
+        inactive.sort();
+        for player_id in inactive {
+            self.away.lock().unwrap().insert(player_id.clone());
+            self.add_table_event(TableEvent::PlayerSatOut{player_id});
+        }
+    }
+
+    fn seat_player(&mut self, player: LivePlayer, max_players: usize) -> Result<(), JoinError> {
+        if self.players.len() >= max_players {
+            return Err(JoinError::Full);
+        }
+        if self.players.contains_key(&player.player_id) {
+            return Err(JoinError::AlreadyJoined);
+        }
+        let player_id = player.player_id.clone();
+        self.players.insert(player_id.clone(), player);
+        let seat = (0..).map(Seat).find(|s| !self.seats.contains_key(s)).unwrap();
+        self.seats.insert(seat, player_id.clone());
+        self.add_table_event(TableEvent::PlayerJoined{player_id});
+        Ok(())
+    }
+
+    fn unseat_player(&mut self, player_id: &PlayerId) -> Option<LivePlayer> {
+        self.seats.retain(|_, p| p != player_id);
+        self.away.lock().unwrap().remove(player_id);
+        self.players.remove(player_id)
+    }
+
+    fn remove_player(&mut self, player_id: &PlayerId) -> Chips {
+        let chips = self.unseat_player(player_id).map(|p| p.chips).unwrap_or(0);
+        *self.cashed_out.entry(player_id.clone()).or_default() += chips;
+        self.add_table_event(TableEvent::PlayerLeft{player_id: player_id.clone(), chips});
+        chips
+    }
+
+    fn refund_carried_pot(&mut self) {
+        let mut carried: Vec<(PlayerId, Chips)> = self.carried_pot.drain().filter(|(_, chips)| *chips > 0).collect();
+        carried.sort();
+        for (player_id, chips) in carried {
+            // Players who already left take it with the rest of their chips
+            match self.players.get_mut(&player_id) {
+                Some(player) => player.chips += chips,
+                None => *self.cashed_out.entry(player_id.clone()).or_default() += chips,
+            }
+            *self.refunds.entry(player_id.clone()).or_default() += chips;
+            self.add_table_event(TableEvent::CarriedPotRefunded{player_id, chips});
+        }
+    }
+
+    fn settle_leaving(&mut self) {
+        let mut leaving: Vec<PlayerId> = self.leaving.lock().unwrap().drain().collect();
+        leaving.sort();
+        for player_id in leaving {
+            self.remove_player(&player_id);
+        }
+    }
+
+    fn is_in_hand(&self, player_id: &PlayerId) -> bool {
+        self.in_hand && self.roles.as_ref().map(|r| r.contains_key(player_id)).unwrap_or(false)
+    }
+
+    fn buy_ins(&self, player_id: &PlayerId, kind: BuyInKind) -> usize {
+        self.ledger.iter().filter(|e| &e.player_id == player_id && e.kind == kind).count()
+    }
+
+    fn can_rebuy(&self, player_id: &PlayerId, rules: &RebuyRules) -> Result<(), BuyInError> {
+        let player = self.players.get(player_id).ok_or(BuyInError::NotJoined)?;
+        if !rules.allowed {
+            return Err(BuyInError::NotAllowed);
+        }
+        if self.is_in_hand(player_id) {
+            return Err(BuyInError::InHand);
+        }
+        if player.chips > 0 {
+            return Err(BuyInError::NotBusted);
+        }
+        if rules.max_rebuys.map(|max| self.buy_ins(player_id, BuyInKind::Rebuy) >= max).unwrap_or(false) {
+            return Err(BuyInError::LimitReached);
+        }
+        Ok(())
+    }
+
+    fn buy_in(&mut self, player_id: &PlayerId, kind: BuyInKind, chips: Chips) {
+        self.players.get_mut(player_id).unwrap().chips += chips;
+        let hand = self.old_logs.len();
+        self.ledger.push(LedgerEntry{player_id: player_id.clone(), kind, chips, hand});
+        if kind != BuyInKind::BuyIn {
+            self.add_table_event(TableEvent::BuyIn{player_id: player_id.clone(), kind, chips});
+        }
+    }
+
+    // Knocks out busted players and records their places. Returns true once one player has every chip
+    fn knock_out(&mut self, prizes: &PrizeStructure, chips_before: &HashMap<PlayerId, Chips>) -> bool {
+        let busted: Vec<(Chips, PlayerId)> = self.players.values().filter(|p| p.chips <= 0).map(|p| (chips_before.get(&p.player_id).copied().unwrap_or(0), p.player_id.clone())).collect();
+        let remaining = self.players.len() - busted.len();
+        let payouts = prizes.payouts(self.ledger.iter().map(|e| e.chips).sum());
+        let payout = |place: usize| payouts.get(place - 1).copied().unwrap_or(0);
+        for (place, player_id) in finishing_places(busted, remaining) {
+            self.unseat_player(&player_id);
+            self.add_table_event(TableEvent::PlayerBusted{player_id: player_id.clone(), place});
+            self.standings.insert(0, Standing{place, player_id, payout: payout(place)});
+        }
+        if remaining > 1 {
+            return false;
+        }
+        if let Some(player_id) = self.players.keys().next().cloned() {
+            self.standings.insert(0, Standing{place: 1, player_id, payout: payout(1)});
+        }
+        self.add_table_event(TableEvent::TournamentFinished{standings: self.standings.clone()});
+        true
+    }
+
+    // Whether enough players have chips, or can rebuy, to keep playing
+    fn can_continue(&self, rules: &RebuyRules) -> bool {
+        self.players.keys().filter(|p| self.players[*p].chips > 0 || self.can_rebuy(p, rules).is_ok()).count() > 1
+    }
+
+    // Players sitting out are dealt in anyway when deal_away is set, and check or fold
+    fn next_round_roles(&self, deal_away: bool) -> HashMap<PlayerRole, (Seat, PlayerId)> {
         let start =
             if let Some(last_dealer_seat) = self.last_dealer {
                 self.next_seat(last_dealer_seat)
@@ -204,7 +703,8 @@ impl TableState {
         let mut role = 0;
         while let Some(sp) = cur {
             cur = self.next_seat(sp.0);
-            if self.players.get(&sp.1).unwrap().chips > 0 {
+            let skipped = if deal_away { self.is_leaving(&sp.1) } else { self.is_away(&sp.1) };
+            if self.players.get(&sp.1).unwrap().chips > 0 && !skipped {
                 retval.insert(role, sp);
                 role += 1;
             }
@@ -215,6 +715,31 @@ impl TableState {
         retval
     }
 
+    // Moves the schedule on for the next hand, returning the time left if it is on a break
+    fn advance_schedule(&mut self) -> Option<Duration> {
+        let hand = self.old_logs.len();
+        let uptime = self.server_uptime();
+        let (idx, entry, break_left) = {
+            let mut clock = self.schedule.as_ref()?.lock().unwrap();
+            clock.advance(hand, uptime);
+            (clock.index(), clock.current(), clock.break_left(uptime))
+        };
+        if idx != self.schedule_idx {
+            self.schedule_idx = idx;
+            if let ScheduleEntry::Break{minutes} = entry {
+                self.add_table_event(TableEvent::BreakStarted{minutes});
+            }
+        }
+        break_left
+    }
+
+    fn next_ante(&self, round: usize) -> AnteRule {
+        match self.schedule.as_ref().map(|clock| clock.lock().unwrap().current()) {
+            Some(ScheduleEntry::Level(level)) => level.ante_rule(),
+            _ => (self.ante_rule)(round, self.server_uptime()),
+        }
+    }
+
     fn server_uptime(&self) -> Duration {
         let now = std::time::Instant::now();
         (now - self.start_time) + self.past_time
@@ -225,6 +750,10 @@ pub type SpecialRules = Vec<SpecialCard>;
 
 impl Table {
     pub fn new(config: TableConfig, rules: TableRules, ante_rule: Box<AnteRuleFn>) -> Table {
+        Table::with_static_config(config, rules, ante_rule, read_static_config())
+    }
+
+    pub fn with_static_config(config: TableConfig, rules: TableRules, ante_rule: Box<AnteRuleFn>, static_config: StaticConfig) -> Table {
         let start_time = std::time::Instant::now();
         let state = TableState {
             rules,
@@ -239,8 +768,21 @@ impl Table {
             start_time,
             past_time: std::time::Duration::from_secs(0),
             ante_rule,
+            schedule: None,
+            schedule_idx: 0,
             cur_log: Vec::new(),
             last_log_read: 0,
+            away: Arc::new(Mutex::new(HashSet::new())),
+            leaving: Arc::new(Mutex::new(HashSet::new())),
+            in_hand: false,
+            ledger: Vec::new(),
+            cashed_out: HashMap::new(),
+            standings: Vec::new(),
+            rake: Vec::new(),
+            bomb_pot_votes: HashSet::new(),
+            carried_pot: HashMap::new(),
+            refunds: HashMap::new(),
+            in_tournament: false,
         };
         let (running_tx, running_rx) = watch::channel(false);
         let (spectator_tx, spectator_rx) = fold_channel::channel(Vec::new(), |v, t: Vec<PokerGlobalViewDiff<PlayerId>>| v.extend_from_slice(&t));
@@ -254,21 +796,27 @@ impl Table {
             spectator_rx,
             table_view_tx,
             table_view_rx,
-            static_config: read_static_config(),
+            static_config,
+            deck: standard_deck().clone(),
         }
     }
 
-    async fn get_next_variant(&self, roles: &HashMap<PlayerRole, PlayerId>) -> (PokerVariant, PokerVariantDesc, SpecialRules) {
+    // Returns None if no variant can be dealt to this many players
+    async fn get_next_variant(&self, roles: &HashMap<PlayerRole, PlayerId>) -> Option<(PokerVariant, PokerVariantDesc, SpecialRules)> {
         let (variant_state, dealer) = {
             let state = self.state.lock().unwrap();
             let variant_state = state.variant_state.clone();
             (variant_state, state.players.get(roles.get(&0).unwrap()).unwrap().clone())
         };
+        // Skip variants there aren't enough cards for with this many players
+        let supported = |desc: &PokerVariantDesc| variant_max_players(&desc.variant(), standard_deck().raw.len()) >= roles.len();
         match variant_state {
             PokerVariantState::Rotation{variants, mut idx} => {
                 if idx >= variants.descs.len() {
                     idx = 0;
                 }
+                let skipped = (0..variants.descs.len()).find(|skip| supported(&variants.descs[(idx + skip) % variants.descs.len()]))?;
+                idx = (idx + skipped) % variants.descs.len();
                 let desc = variants.descs.get(idx).unwrap().clone();
                 let retval = desc.variant();
                 {
@@ -277,14 +825,18 @@ impl Table {
                     state.variant_state = PokerVariantState::Rotation{variants, idx};
                 }
                 let special_cards = desc.special_cards.iter().flat_map(|desc| SpecialCardGroup::from(desc).cards).collect();
-                (retval, desc, special_cards)
+                Some((retval, desc, special_cards))
             },
             PokerVariantState::DealersChoice{variants} => {
-                let DealersChoiceResp{variant_idx: idx, special_cards} = dealer.input.dealers_choice(variants.descs.clone()).await;
-                let mut desc = variants.descs.get(idx).unwrap().clone();
+                let offered: Vec<PokerVariantDesc> = variants.descs.iter().filter(|desc| supported(desc)).cloned().collect();
+                if offered.is_empty() {
+                    return None;
+                }
+                let DealersChoiceResp{variant_idx: idx, special_cards} = dealer.input.dealers_choice(offered.clone()).await;
+                let mut desc = offered.get(idx).unwrap().clone();
                 let special_cards_cards = special_cards.iter().flat_map(|&idx| SpecialCardGroup::from(desc.special_cards.get(idx).unwrap()).cards).collect();
                 desc.special_cards = special_cards.iter().map(|idx| desc.special_cards.get(*idx).cloned().unwrap()).collect();
-                (desc.variant(), desc, special_cards_cards)
+                Some((desc.variant(), desc, special_cards_cards))
             },
         }
     }
@@ -300,25 +852,76 @@ impl Table {
             }
         }
         println!("Next round starting...");
-        let (roles, players, round) = {
+        let break_left = {
+            let mut state = self.state.lock().unwrap();
+            let break_left = state.advance_schedule();
+            self.table_view_tx.send(self.viewstate(&state));
+            break_left
+        };
+        if let Some(break_left) = break_left {
+            tokio::time::sleep(break_left).await;
+            return true;
+        }
+        let enough_players = {
+            let mut state = self.state.lock().unwrap();
+            state.sit_out_inactive();
+            self.table_view_tx.send(self.viewstate(&state));
+            state.next_round_roles(self.chips_locked(&state)).len() > 1
+        };
+        if !enough_players {
+            // Wait for players to sit back in or join
+            tokio::time::sleep(Duration::from_millis(self.static_config.ms_between_rounds)).await;
+            let mut state = self.state.lock().unwrap();
+            self.refund_if_stopped(&mut state);
+            self.table_view_tx.send(self.viewstate(&state));
+            return state.can_continue(&self.config.rebuys);
+        }
+        let (roles, players, round, last_dealer) = {
             let mut state = self.state.lock().unwrap();
-            let roles = state.next_round_roles();
+            // Players can't take their chips out of a sit-and-go or tournament by sitting out, so they blind off
+            let roles = state.next_round_roles(self.chips_locked(&state));
+            let last_dealer = state.last_dealer;
             state.last_dealer = roles.get(&0).map(|(s, p)| *s);
             let just_roles: HashMap<PlayerRole, PlayerId> = roles.into_iter().map(|(r, (s, p))| (r, p)).collect();
             state.roles = Some(just_roles.iter().map(|(&r, p)| (p.clone(), r)).collect());
-            let players: HashMap<PlayerRole, LivePlayer> = just_roles.iter().map(|(&r, p)| (r, state.players.get(p).cloned().unwrap())).collect();
+            state.in_hand = true;
+            let players: HashMap<PlayerRole, LivePlayer> = just_roles.iter().map(|(&r, p)| {
+                let player = state.players.get(p).cloned().unwrap();
+                let input = Arc::new(SeatedInput {
+                    player_id: p.clone(),
+                    input: player.input,
+                    away: state.away.clone(),
+                    leaving: state.leaving.clone(),
+                    bet_this_round: Mutex::new(0),
+                });
+                (r, LivePlayer{input, ..player})
+            }).collect();
             self.table_view_tx.send(self.viewstate(&state));
             let round = state.old_logs.len();
-            (just_roles, players, round)
+            (just_roles, players, round, last_dealer)
         };
-        let mut deck = Box::new(standard_deck().clone());
+        let mut deck = Box::new(self.deck.clone());
         {
             let mut rng = rand::thread_rng();
             deck.secure_shuffle(&mut rng);
         }
         println!("Getting variant");
-        let (variant, variant_desc, special_cards) = self.get_next_variant(&roles).await;
-        let rules = {
+        let (variant, variant_desc, special_cards) = match self.get_next_variant(&roles).await {
+            Some(next_variant) => next_variant,
+            None => {
+                // Nothing can be dealt until some players leave, so stop instead of retrying every hand
+                let mut state = self.state.lock().unwrap();
+                state.last_dealer = last_dealer;
+                state.in_hand = false;
+                state.add_table_event(TableEvent::NoVariantFits{players: roles.len()});
+                drop(state);
+                self.stop();
+                let state = self.state.lock().unwrap();
+                self.table_view_tx.send(self.viewstate(&state));
+                return true;
+            }
+        };
+        let (rules, bomb_pot, carried_pot) = {
             println!("Got variant");
             let mut state = self.state.lock().unwrap();
             println!("Locked state");
@@ -327,19 +930,17 @@ impl Table {
             self.table_view_tx.send(self.viewstate(&state));
             println!("Sent viewstate");
 
-            state.rules.ante = (state.ante_rule)(round, state.server_uptime());
+            let old_ante = state.rules.ante.clone();
+            state.rules.ante = state.next_ante(round);
             println!("Got ante rule");
             state.buttons.clear();
             state.buttons.insert(roles.get(&0).cloned().unwrap(), PokerButton::Dealer);
             let new_min_bet = match state.rules.ante.clone() {
                 AnteRule::Ante(ante) => ante,
-                AnteRule::Blinds(blinds) => {
+                AnteRule::BringIn{bring_in, ..} => bring_in * 2,
+                AnteRule::Blinds(blinds) | AnteRule::BlindsAnte{blinds, ..} => {
                     let max_blind = blinds.iter().map(|b| b.amount).max().unwrap();
-                    let mut blind_role = 0;
-
-                    if state.players.values().filter(|p| p.chips > 0).count() > 2 {
-                        blind_role = 1;
-                    }
+                    let mut blind_role = blind_starting_player(roles.len());
 
                     for Blind{amount} in blinds {
                         state.buttons.insert(roles.get(&blind_role).cloned().unwrap(), if amount == max_blind {PokerButton::BigBlind} else {PokerButton::SmallBlind});
@@ -349,24 +950,51 @@ impl Table {
                     max_blind
                 },
             };
-            if new_min_bet != state.rules.min_bet {
+            if new_min_bet != state.rules.min_bet || state.rules.ante != old_ante {
+                state.rules.betting = state.rules.betting.rescaled(state.rules.min_bet, new_min_bet);
                 state.rules.min_bet = new_min_bet;
                 let send_rules = state.rules.clone();
                 state.add_table_event(TableEvent::AnteChange{new_table_rules: send_rules});
             }
+            let bomb_pot = state.rules.bomb_pot.and_then(|rule| {
+                let scheduled = rule.every_hands.map(|hands| (round + 1) % hands == 0).unwrap_or(false);
+                let votes = roles.values().filter(|p| state.bomb_pot_votes.contains(*p)).count();
+                if scheduled || (rule.by_vote && votes * 2 > roles.len()) {
+                    Some(rule.ante)
+                } else {
+                    None
+                }
+            });
+            if let Some(ante) = bomb_pot {
+                state.bomb_pot_votes.clear();
+                state.add_table_event(TableEvent::BombPot{ante});
+            }
             self.table_view_tx.send(self.viewstate(&state));
-            state.rules.clone()
+            (state.rules.clone(), bomb_pot, state.carried_pot.values().sum())
         };
+        let mut extras = HandExtras{straddle: None, bomb_pot, carried_pot};
+        if let (Some(rule), None) = (rules.straddle, bomb_pot) {
+            if let Some((role, amount)) = straddle_offer(rule, &rules.ante, roles.len()) {
+                let player = players.get(&role).unwrap();
+                if player.chips > amount && player.input.straddle(amount).await {
+                    extras.straddle = Some((role, amount));
+                    let mut state = self.state.lock().unwrap();
+                    state.add_table_event(TableEvent::Straddle{player_id: player.player_id.clone(), chips: amount});
+                    self.table_view_tx.send(self.viewstate(&state));
+                }
+            }
+        }
         println!("Playing poker...");
         match play_poker(variant,
             Mutex::new(deck),
             players,
             Some(self.spectator_tx.clone()),
             rules,
+            extras,
             special_cards,
             round,
             ).await {
-            Ok(winners) => {
+            Ok(HandResult{winnings: winners, carried_pot: carried_out}) => {
                 tokio::time::sleep(Duration::from_millis(self.static_config.ms_between_rounds)).await;
                 let mut state = self.state.lock().unwrap();
                 {
@@ -374,22 +1002,80 @@ impl Table {
                     state.add_hand_logs(old_log);
                     state.new_round();
                 }
+                // Whatever the winners were paid short of the pot went to the rake
+                let rake = carried_pot - winners.values().sum::<Chips>() - carried_out;
+                if carried_out > 0 {
+                    for (role, change) in &winners {
+                        *state.carried_pot.entry(roles[role].clone()).or_default() -= change;
+                    }
+                } else {
+                    state.carried_pot.clear();
+                }
+                if rake > 0 {
+                    state.rake.push(RakeEntry{hand: round, chips: rake});
+                }
+                let chips_before: HashMap<PlayerId, Chips> = state.players.iter().map(|(id, p)| (id.clone(), p.chips)).collect();
                 for (role, change) in winners.into_iter() {
                     state.players.get_mut(roles.get(&role).unwrap()).unwrap().chips += change;
                 }
+                state.in_hand = false;
+                state.settle_leaving();
                 state.running_variant = None;
+                let finished = self.config.sit_and_go.as_ref().map(|prizes| state.knock_out(prizes, &chips_before)).unwrap_or(false);
+                self.refund_if_stopped(&mut state);
                 self.table_view_tx.send(self.viewstate(&state));
+                if finished {
+                    drop(state);
+                    self.stop();
+                    return false;
+                }
             }
-            Err(message) => {
-                println!("Error in play_poker, rolling back game {}", message);
+            Err(reason) => {
+                println!("Error in play_poker, aborting hand {}", reason);
+                // Winnings are only paid out once the hand finishes, so everyone still has the chips they started with
+                let mut state = self.state.lock().unwrap();
+                {
+                    let old_log = &self.spectator_rx.borrow()[..];
+                    state.add_hand_logs(old_log);
+                }
+                state.add_table_event(TableEvent::HandAborted{reason});
+                state.in_hand = false;
+                state.settle_leaving();
+                state.new_round();
+                state.running_variant = None;
+                self.refund_if_stopped(&mut state);
+                self.table_view_tx.send(self.viewstate(&state));
             }
         }
         {
             let state = self.state.lock().unwrap();
-            state.players.values().filter(|p| p.chips > 0).count() > 1
+            state.can_continue(&self.config.rebuys)
         }
     }
 
+    pub fn set_schedule(&self, schedule: BlindSchedule) {
+        self.share_schedule(Arc::new(Mutex::new(ScheduleClock::new(schedule))));
+    }
+
+    pub fn share_schedule(&self, clock: Arc<Mutex<ScheduleClock>>) {
+        let mut state = self.state.lock().unwrap();
+        state.schedule = Some(clock);
+        self.table_view_tx.send(self.viewstate(&state));
+    }
+
+    pub fn set_tournament(&self) {
+        self.state.lock().unwrap().in_tournament = true;
+    }
+
+    // Whether players must stay until they bust or the game ends
+    fn chips_locked(&self, state: &TableState) -> bool {
+        state.in_tournament || self.is_sit_and_go_started(state)
+    }
+
+    pub fn config(&self) -> &TableConfig {
+        &self.config
+    }
+
     pub fn logs(&self, player_id: Option<&PlayerId>, start_from: usize) -> Vec<PokerLogUpdate> {
         let mut state = self.state.lock().unwrap();
         let cur_log = self.spectator_rx.borrow();
@@ -398,25 +1084,197 @@ impl Table {
     }
 
     pub fn join(&self, player_id: PlayerId, player: Arc<PlayerInputSource>) -> Result<(), JoinError> {
+        let full = {
+            let mut state = self.state.lock().unwrap();
+            if self.is_sit_and_go_started(&state) {
+                return Err(JoinError::Started);
+            }
+            state.seat_player(LivePlayer {
+                player_id: player_id.clone(),
+                chips: 0,
+                input: player
+            }, self.config.max_players)?;
+            state.buy_in(&player_id, BuyInKind::BuyIn, self.config.starting_chips);
+            self.table_view_tx.send(self.viewstate(&state));
+            state.players.len() == self.config.max_players
+        };
+        if full && self.config.sit_and_go.is_some() {
+            self.start();
+        }
+        Ok(())
+    }
+
+    fn is_sit_and_go_started(&self, state: &TableState) -> bool {
+        self.config.sit_and_go.is_some() && (*self.running_rx.borrow() || !state.old_logs.is_empty())
+    }
+
+    // Seats a player who brings their own chips, e.g. one moved from another table
+    pub fn seat_player(&self, player: LivePlayer) -> Result<(), JoinError> {
         let mut state = self.state.lock().unwrap();
-        if state.players.len() >= self.config.max_players {
-            return Err(JoinError::Full);
+        state.seat_player(player, self.config.max_players)?;
+        self.table_view_tx.send(self.viewstate(&state));
+        Ok(())
+    }
+
+    // Takes a player who isn't in a hand off the table along with their chips
+    pub fn take_player(&self, player_id: &PlayerId, event: TableEvent) -> Result<LivePlayer, SeatError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.players.contains_key(player_id) {
+            return Err(SeatError::NotJoined);
         }
-        if state.players.contains_key(&player_id) {
-            return Err(JoinError::AlreadyJoined);
+        if state.is_in_hand(player_id) {
+            return Err(SeatError::InHand);
         }
-        state.players.insert(player_id.clone(), LivePlayer {
-            player_id: player_id.clone(),
-            chips: self.config.starting_chips,
-            input: player
-        });
-        let seat_len = state.seats.len();
-        state.seats.insert(Seat(seat_len), player_id.clone());
-        state.add_table_event(TableEvent::PlayerJoined{player_id});
+        if state.is_leaving(player_id) {
+            return Err(SeatError::Leaving);
+        }
+        let player = state.unseat_player(player_id).unwrap();
+        state.add_table_event(event);
         self.table_view_tx.send(self.viewstate(&state));
+        Ok(player)
+    }
+
+    // Players who could be taken off the table right now, in seat order
+    pub fn movable_players(&self) -> Vec<PlayerId> {
+        let state = self.state.lock().unwrap();
+        state.seats.values().filter(|p| !state.is_in_hand(p) && !state.is_leaving(p)).cloned().collect()
+    }
+
+    // The sit-and-go prizes, by finishing place. The table starts full, so every seat has bought in
+    pub fn sit_and_go_payouts(&self) -> Option<Vec<Chips>> {
+        let prizes = self.config.sit_and_go.as_ref()?;
+        Some(prizes.payouts(self.config.max_players as Chips * self.config.starting_chips))
+    }
+
+    pub fn chips(&self) -> HashMap<PlayerId, Chips> {
+        let state = self.state.lock().unwrap();
+        state.players.iter().map(|(id, p)| (id.clone(), p.chips)).collect()
+    }
+
+    pub fn log_event(&self, event: TableEvent) {
+        let mut state = self.state.lock().unwrap();
+        state.add_table_event(event);
+        self.table_view_tx.send(self.viewstate(&state));
+    }
+
+    pub fn sit_out(&self, player_id: &PlayerId) -> Result<(), SeatError> {
+        let mut state = self.state.lock().unwrap();
+        let input = state.players.get(player_id).ok_or(SeatError::NotJoined)?.input.clone();
+        if state.is_leaving(player_id) {
+            return Err(SeatError::Leaving);
+        }
+        input.set_sitting_out(true);
+        if state.away.lock().unwrap().insert(player_id.clone()) {
+            state.add_table_event(TableEvent::PlayerSatOut{player_id: player_id.clone()});
+            self.table_view_tx.send(self.viewstate(&state));
+        }
         Ok(())
     }
 
+    // A bomb pot is dealt once a majority of the players in the next hand have voted for one
+    pub fn vote_bomb_pot(&self, player_id: &PlayerId) -> Result<(), SeatError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.players.contains_key(player_id) {
+            return Err(SeatError::NotJoined);
+        }
+        if !state.rules.bomb_pot.map(|rule| rule.by_vote).unwrap_or(false) {
+            return Err(SeatError::NotAllowed);
+        }
+        if state.bomb_pot_votes.insert(player_id.clone()) {
+            state.add_table_event(TableEvent::BombPotVote{player_id: player_id.clone()});
+            self.table_view_tx.send(self.viewstate(&state));
+        }
+        Ok(())
+    }
+
+    pub fn sit_in(&self, player_id: &PlayerId) -> Result<(), SeatError> {
+        let mut state = self.state.lock().unwrap();
+        let input = state.players.get(player_id).ok_or(SeatError::NotJoined)?.input.clone();
+        if state.is_leaving(player_id) {
+            return Err(SeatError::Leaving);
+        }
+        input.set_sitting_out(false);
+        if state.away.lock().unwrap().remove(player_id) {
+            state.add_table_event(TableEvent::PlayerSatIn{player_id: player_id.clone()});
+            self.table_view_tx.send(self.viewstate(&state));
+        }
+        Ok(())
+    }
+
+    // Returns the chips the player cashed out with, or None if they are in a hand and will
+    // be removed once it is settled
+    pub fn leave(&self, player_id: &PlayerId) -> Result<Option<Chips>, SeatError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.players.contains_key(player_id) {
+            return Err(SeatError::NotJoined);
+        }
+        if state.is_leaving(player_id) {
+            return Err(SeatError::Leaving);
+        }
+        // Chips can't leave a sit-and-go or tournament, so players who go blind off instead
+        if self.chips_locked(&state) {
+            return Err(SeatError::NotAllowed);
+        }
+        let retval = if state.is_in_hand(player_id) {
+            state.leaving.lock().unwrap().insert(player_id.clone());
+            None
+        } else {
+            Some(state.remove_player(player_id))
+        };
+        self.refund_if_stopped(&mut state);
+        self.table_view_tx.send(self.viewstate(&state));
+        Ok(retval)
+    }
+
+    pub fn rebuy(&self, player_id: &PlayerId) -> Result<Chips, BuyInError> {
+        let mut state = self.state.lock().unwrap();
+        state.can_rebuy(player_id, &self.config.rebuys)?;
+        let amount = self.config.rebuys.amount;
+        state.buy_in(player_id, BuyInKind::Rebuy, amount);
+        self.table_view_tx.send(self.viewstate(&state));
+        Ok(amount)
+    }
+
+    pub fn add_on(&self, player_id: &PlayerId) -> Result<Chips, BuyInError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.players.contains_key(player_id) {
+            return Err(BuyInError::NotJoined);
+        }
+        let add_on = self.config.rebuys.add_on.ok_or(BuyInError::NotAllowed)?;
+        if state.old_logs.len() >= add_on.until_hand {
+            return Err(BuyInError::WindowClosed);
+        }
+        if state.is_in_hand(player_id) {
+            return Err(BuyInError::InHand);
+        }
+        if state.buy_ins(player_id, BuyInKind::AddOn) > 0 {
+            return Err(BuyInError::LimitReached);
+        }
+        state.buy_in(player_id, BuyInKind::AddOn, add_on.amount);
+        self.table_view_tx.send(self.viewstate(&state));
+        Ok(add_on.amount)
+    }
+
+    pub fn ledger(&self) -> Vec<LedgerEntry> {
+        self.state.lock().unwrap().ledger.clone()
+    }
+
+    pub fn rake_ledger(&self) -> Vec<RakeEntry> {
+        self.state.lock().unwrap().rake.clone()
+    }
+
+    pub fn settlement(&self) -> Result<Settlement, String> {
+        if *self.running_rx.borrow() {
+            return Err("The table must be stopped to settle up".to_string());
+        }
+        let state = self.state.lock().unwrap();
+        let mut final_chips = state.cashed_out.clone();
+        for (player_id, player) in &state.players {
+            *final_chips.entry(player_id.clone()).or_default() += player.chips;
+        }
+        Ok(settle(&state.ledger, &final_chips, &state.refunds))
+    }
+
     pub fn start(&self) {
         self.running_tx.send(true);
         {
@@ -426,14 +1284,30 @@ impl Table {
     }
 
     pub fn stop(&self) {
-        let was_running = self.running_rx.borrow();
+        let was_running = *self.running_rx.borrow();
         self.running_tx.send(false);
-        if *was_running {
-            let mut state = self.state.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        if was_running {
             let now = std::time::Instant::now();
             let start_time = state.start_time;
             state.past_time += now - start_time;
         }
+        self.refund_if_stopped(&mut state);
+        self.table_view_tx.send(self.viewstate(&state));
+    }
+
+    // A carried pot can only be won by another hand, so it goes back to the players who put it in
+    // once the table stops or can't deal one
+    fn refund_if_stopped(&self, state: &mut TableState) {
+        if !state.in_hand && (!*self.running_rx.borrow() || !state.can_continue(&self.config.rebuys)) {
+            state.refund_carried_pot();
+        }
+    }
+
+    // Like the last sent viewstate, but with the level clock up to date
+    pub fn current_viewstate(&self) -> TableViewState {
+        let state = self.state.lock().unwrap();
+        self.viewstate(&state)
     }
 
     fn viewstate(&self, state: &TableState) -> TableViewState {
@@ -448,6 +1322,12 @@ impl Table {
             seats: state.seats.iter().map(|(s, p)| (p.clone(), *s)).collect(),
             config: config.clone(),
             running_variant: state.running_variant.clone(),
+            level: state.schedule.as_ref().map(|clock| clock.lock().unwrap().view(state.old_logs.len(), state.server_uptime())),
+            sitting_out: {
+                let mut sitting_out: Vec<PlayerId> = state.away.lock().unwrap().iter().cloned().collect();
+                sitting_out.sort();
+                sitting_out
+            },
         }
     }
 }
@@ -465,3 +1345,224 @@ impl From<PokerVariantSelector> for PokerVariantState {
         }
     }
 }
+
+mod test {
+    use crate::table::*;
+    use crate::bot::*;
+    use crate::bot_always_call::*;
+
+    fn test_table(config: TableConfig) -> Table {
+        let blinds = AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]);
+        let rules = TableRules {
+            ante: blinds.clone(),
+            ante_name: "Blind".to_string(),
+            min_bet: 2,
+            betting: BettingStructure::NoLimit,
+            rake: None,
+            straddle: None,
+            bomb_pot: None,
+            run_it: None,
+            all_in_pause_ms: 0,
+        };
+        let static_config = StaticConfig {
+            serve_address: String::new(),
+            serve_port: 0,
+            tls: false,
+            cert_path: String::new(),
+            key_path: String::new(),
+            ms_between_rounds: 0,
+            variants_dir: None,
+        };
+        Table::with_static_config(config, rules, Box::new(move |_, _| blinds.clone()), static_config)
+    }
+
+    fn hold_em_config() -> TableConfig {
+        TableConfig {
+            max_players: 4,
+            starting_chips: 100,
+            variant_selector: PokerVariantSelector::Rotation(PokerVariants{descs: vec![PokerVariantDesc{name: "Texas Hold 'Em".to_string(), special_cards: Vec::new()}]}),
+            timer: ActionTimer::default(),
+            rebuys: RebuyRules::default(),
+            sit_and_go: None,
+        }
+    }
+
+    fn join_bot(table: &Table, player_id: &str) {
+        assert!(table.join(player_id.to_string(), Arc::new(BotInputSource::new(Arc::new(BotAlwaysCall::new())))).is_ok());
+    }
+
+    // Leaves the table the first time it's asked to bet, then calls
+    struct LeaveOnBet {
+        player_id: PlayerId,
+        table: Mutex<Option<Arc<Table>>>,
+    }
+
+    #[async_trait]
+    impl PlayerInputSource for LeaveOnBet {
+        async fn bet(&self, call_amount: Chips, _min_bet: Chips, _max_bet: Chips) -> BetResp {
+            if let Some(table) = self.table.lock().unwrap().take() {
+                assert_eq!(table.leave(&self.player_id), Ok(None));
+            }
+            BetResp::Bet(call_amount)
+        }
+
+        async fn replace(&self, _max_can_replace: usize) -> ReplaceResp {
+            Vec::new()
+        }
+
+        async fn dealers_choice(&self, _variants: Vec<PokerVariantDesc>) -> DealersChoiceResp {
+            DealersChoiceResp::default()
+        }
+
+        fn update(&self, _update: PokerViewUpdate) {}
+    }
+
+    fn table_events(table: &Table) -> Vec<TableEvent> {
+        table.logs(None, 0).into_iter().flat_map(|update| update.log).filter_map(|diff| match diff {
+            TableViewDiff::TableDiff(event) => Some(event),
+            TableViewDiff::GameDiff(_) => None,
+        }).collect()
+    }
+
+    // Bots block in place to think, which needs the multi-threaded runtime
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_hand_aborted() {
+        let mut table = test_table(hold_em_config());
+        // Enough for the hole cards but not the flop
+        table.deck = VecDeck{raw: standard_deck().raw[..5].to_vec()};
+        join_bot(&table, "0");
+        join_bot(&table, "1");
+        table.start();
+        assert!(table.next_round().await);
+        // The blinds were posted, but nothing is paid out of an aborted hand
+        assert_eq!(table.chips(), vec![("0".to_string(), 100), ("1".to_string(), 100)].into_iter().collect());
+        assert!(table_events(&table).iter().any(|event| matches!(event, TableEvent::HandAborted{..})));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_carried_pot_refunded() {
+        let config = TableConfig {
+            variant_selector: PokerVariantSelector::Rotation(PokerVariants{descs: vec![PokerVariantDesc{name: "Jacks or Better".to_string(), special_cards: Vec::new()}]}),
+            ..hold_em_config()
+        };
+        let mut table = test_table(config);
+        // No pairs, straights or flushes, so nobody can open
+        table.deck = VecDeck{raw: [1, 2, 3, 5, 6, 7, 9, 10, 11, 12].iter().enumerate().map(|(idx, &rank)| Card{rank, suit: Suit(idx % NUM_SUITS)}).collect()};
+        join_bot(&table, "0");
+        join_bot(&table, "1");
+        table.start();
+        assert!(table.next_round().await);
+        assert_eq!(table.chips(), vec![("0".to_string(), 98), ("1".to_string(), 98)].into_iter().collect());
+        assert_eq!(table.settlement().err(), Some("The table must be stopped to settle up".to_string()));
+
+        // Nobody can win the pot once the table stops, so the blinds go back
+        table.stop();
+        assert_eq!(table.chips(), vec![("0".to_string(), 100), ("1".to_string(), 100)].into_iter().collect());
+        let refunds: Vec<(PlayerId, Chips)> = table_events(&table).into_iter().filter_map(|event| match event {
+            TableEvent::CarriedPotRefunded{player_id, chips} => Some((player_id, chips)),
+            _ => None,
+        }).collect();
+        assert_eq!(refunds, vec![("0".to_string(), 2), ("1".to_string(), 2)]);
+        let settlement = table.settlement().unwrap();
+        assert_eq!(settlement.balances.iter().map(|b| (b.net, b.refunded)).collect::<Vec<_>>(), vec![(0, 2), (0, 2)]);
+        assert!(settlement.transfers.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sit_out_sit_in() {
+        let table = test_table(hold_em_config());
+        for player_id in ["0", "1", "2"] {
+            join_bot(&table, player_id);
+        }
+        let player_id = "2".to_string();
+        assert_eq!(table.sit_out(&"3".to_string()), Err(SeatError::NotJoined));
+        assert_eq!(table.sit_out(&player_id), Ok(()));
+        assert_eq!(table.sit_out(&player_id), Ok(()));
+        assert_eq!(table.current_viewstate().sitting_out, vec![player_id.clone()]);
+        table.start();
+        assert!(table.next_round().await);
+        // Dealt out of the hand entirely
+        assert_eq!(table.chips()[&player_id], 100);
+        assert_eq!(table.current_viewstate().roles.unwrap().len(), 2);
+        assert_eq!(table.sit_in(&player_id), Ok(()));
+        assert!(table.current_viewstate().sitting_out.is_empty());
+        assert!(table.next_round().await);
+        assert_eq!(table.current_viewstate().roles.unwrap().len(), 3);
+        let seat_events: Vec<TableEvent> = table_events(&table).into_iter().filter(|event| matches!(event, TableEvent::PlayerSatOut{..} | TableEvent::PlayerSatIn{..})).collect();
+        assert_eq!(seat_events, vec![TableEvent::PlayerSatOut{player_id: player_id.clone()}, TableEvent::PlayerSatIn{player_id}]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_leave() {
+        let table = test_table(hold_em_config());
+        join_bot(&table, "0");
+        join_bot(&table, "1");
+        let player_id = "1".to_string();
+        assert_eq!(table.leave(&player_id), Ok(Some(100)));
+        assert_eq!(table.leave(&player_id), Err(SeatError::NotJoined));
+        assert!(!table.chips().contains_key(&player_id));
+        assert!(table_events(&table).contains(&TableEvent::PlayerLeft{player_id: player_id.clone(), chips: 100}));
+        // Coming back is a new buy-in
+        join_bot(&table, "1");
+        assert_eq!(table.ledger().iter().filter(|e| e.player_id == player_id).count(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_leave_mid_hand() {
+        let table = Arc::new(test_table(hold_em_config()));
+        join_bot(&table, "0");
+        let player_id = "1".to_string();
+        let leaver = Arc::new(LeaveOnBet{player_id: player_id.clone(), table: Mutex::new(Some(table.clone()))});
+        assert!(table.join(player_id.clone(), leaver).is_ok());
+        table.start();
+        // The small blind calls, and the big blind leaves when they could check. They fold instead
+        assert!(!table.next_round().await);
+        assert_eq!(table.chips(), vec![("0".to_string(), 102)].into_iter().collect());
+        assert!(table_events(&table).contains(&TableEvent::PlayerLeft{player_id, chips: 98}));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rebuy_add_on() {
+        let mut config = hold_em_config();
+        config.rebuys = RebuyRules {
+            allowed: true,
+            max_rebuys: Some(1),
+            amount: 50,
+            add_on: Some(AddOn{amount: 30, until_hand: 1}),
+        };
+        let table = test_table(config);
+        join_bot(&table, "0");
+        join_bot(&table, "1");
+        let (p0, p1) = ("0".to_string(), "1".to_string());
+        assert_eq!(table.rebuy(&"2".to_string()), Err(BuyInError::NotJoined));
+        assert_eq!(table.rebuy(&p0), Err(BuyInError::NotBusted));
+        table.state.lock().unwrap().players.get_mut(&p0).unwrap().chips = 0;
+        assert_eq!(table.rebuy(&p0), Ok(50));
+        table.state.lock().unwrap().players.get_mut(&p0).unwrap().chips = 0;
+        assert_eq!(table.rebuy(&p0), Err(BuyInError::LimitReached));
+        assert_eq!(table.add_on(&p0), Ok(30));
+        assert_eq!(table.add_on(&p0), Err(BuyInError::LimitReached));
+        table.start();
+        assert!(table.next_round().await);
+        // The window closes once until_hand hands have been played
+        assert_eq!(table.add_on(&p1), Err(BuyInError::WindowClosed));
+        assert_eq!(table.ledger(), vec![
+            LedgerEntry{player_id: p0.clone(), kind: BuyInKind::BuyIn, chips: 100, hand: 0},
+            LedgerEntry{player_id: p1, kind: BuyInKind::BuyIn, chips: 100, hand: 0},
+            LedgerEntry{player_id: p0.clone(), kind: BuyInKind::Rebuy, chips: 50, hand: 0},
+            LedgerEntry{player_id: p0, kind: BuyInKind::AddOn, chips: 30, hand: 0},
+        ]);
+        let buy_ins: Vec<TableEvent> = table_events(&table).into_iter().filter(|event| matches!(event, TableEvent::BuyIn{..})).collect();
+        assert_eq!(buy_ins.len(), 2);
+    }
+
+    #[test]
+    fn test_rebuys_not_allowed() {
+        let table = test_table(hold_em_config());
+        join_bot(&table, "0");
+        let player_id = "0".to_string();
+        table.state.lock().unwrap().players.get_mut(&player_id).unwrap().chips = 0;
+        assert_eq!(table.rebuy(&player_id), Err(BuyInError::NotAllowed));
+        assert_eq!(table.add_on(&player_id), Err(BuyInError::NotAllowed));
+    }
+}
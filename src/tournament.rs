@@ -0,0 +1,416 @@
+use crate::game::*;
+use crate::table::*;
+use crate::blind_schedule::*;
+use crate::viewstate::*;
+use crate::server::TableId;
+
+use ts_rs::{TS, export};
+use serde::{Serialize, Deserialize};
+
+use std::collections::{HashMap, BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct PrizeStructure {
+    // Percent of the prize pool for first place, second place, and so on
+    pub percentages: Vec<u32>,
+}
+
+impl PrizeStructure {
+    pub fn is_valid(&self) -> bool {
+        !self.percentages.is_empty() && self.percentages.iter().sum::<u32>() == 100
+    }
+
+    // Rounds each payout down and gives the leftover chips to first place
+    pub fn payouts(&self, prize_pool: Chips) -> Vec<Chips> {
+        let mut retval: Vec<Chips> = self.percentages.iter().map(|&p| prize_pool * p as Chips / 100).collect();
+        if let Some(first) = retval.first_mut() {
+            *first += prize_pool - self.percentages.iter().map(|&p| prize_pool * p as Chips / 100).sum::<Chips>();
+        }
+        retval
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct Standing {
+    pub place: usize,
+    pub player_id: PlayerId,
+    pub payout: Chips,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct TournamentConfig {
+    // Used for every table in the tournament
+    pub table_config: TableConfig,
+    // Shared by every table, so the levels must be timed rather than counted in hands
+    pub schedule: BlindSchedule,
+    #[serde(default)]
+    pub betting: BettingStructure,
+    pub prizes: PrizeStructure,
+}
+
+impl TournamentConfig {
+    pub fn is_valid(&self) -> bool {
+        let timed = self.schedule.entries.iter().all(|e| !matches!(e, ScheduleEntry::Level(BlindLevel{duration: LevelDuration::Hands(_), ..})));
+        self.table_config.is_valid()
+            && self.table_config.max_players >= 2
+            && self.table_config.sit_and_go.is_none()
+            // Every chip comes from a buy-in, so the prize pool is what everyone registered with
+            && !self.table_config.rebuys.allowed
+            && self.table_config.rebuys.add_on.is_none()
+            && self.schedule.is_valid()
+            && timed
+            && self.betting.is_valid()
+            && self.prizes.is_valid()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(TS)]
+pub struct TournamentViewState {
+    pub registered: Vec<PlayerId>,
+    pub started: bool,
+    pub finished: bool,
+    pub seating: HashMap<PlayerId, TableId>,
+    // Best place first
+    pub standings: Vec<Standing>,
+}
+
+// Deals players out around as few tables as possible, as evenly as possible
+pub fn initial_seating(players: &[PlayerId], max_per_table: usize) -> Vec<Vec<PlayerId>> {
+    let num_tables = std::cmp::max(1, (players.len() + max_per_table - 1) / max_per_table);
+    let mut retval = vec![Vec::new(); num_tables];
+    for (idx, player_id) in players.iter().enumerate() {
+        retval[idx % num_tables].push(player_id.clone());
+    }
+    retval
+}
+
+// Places players who busted in the same hand, worst first. Whoever started the hand with fewer
+// chips finishes lower. `remaining` counts the players still in
+pub fn finishing_places(mut busted: Vec<(Chips, PlayerId)>, remaining: usize) -> Vec<(usize, PlayerId)> {
+    busted.sort();
+    let last = remaining + busted.len();
+    busted.into_iter().enumerate().map(|(idx, (_, player_id))| (last - idx, player_id)).collect()
+}
+
+// Returns (from, to) for each player that should move. Breaks the smallest tables while the
+// players fit at fewer, then evens out the rest so no table has more than one extra player
+pub fn plan_moves(counts: &BTreeMap<TableId, usize>, max_per_table: usize) -> Vec<(TableId, TableId)> {
+    let mut counts = counts.clone();
+    let mut retval = Vec::new();
+    let total: usize = counts.values().sum();
+    let needed = std::cmp::max(1, (total + max_per_table - 1) / max_per_table);
+    // Ties break the newest table and fill the oldest
+    let smallest = |counts: &BTreeMap<TableId, usize>| counts.iter().min_by_key(|(&t, &c)| (c, t)).map(|(&t, _)| t).unwrap();
+    while counts.len() > needed {
+        let (&broken, &players) = counts.iter().min_by_key(|(&t, &c)| (c, std::cmp::Reverse(t))).unwrap();
+        counts.remove(&broken);
+        for _ in 0..players {
+            let to = smallest(&counts);
+            *counts.get_mut(&to).unwrap() += 1;
+            retval.push((broken, to));
+        }
+    }
+    loop {
+        let (&from, &most) = counts.iter().max_by_key(|(&t, &c)| (c, std::cmp::Reverse(t))).unwrap();
+        let to = smallest(&counts);
+        if most <= counts[&to] + 1 {
+            break;
+        }
+        *counts.get_mut(&from).unwrap() -= 1;
+        *counts.get_mut(&to).unwrap() += 1;
+        retval.push((from, to));
+    }
+    retval
+}
+
+struct TournamentState {
+    registered: Vec<PlayerId>,
+    started: bool,
+    tables: BTreeSet<TableId>,
+    // Chips after each player's last hand, to place players who bust in the same hand
+    last_chips: HashMap<PlayerId, Chips>,
+    standings: Vec<Standing>,
+    finished: bool,
+}
+
+pub struct Tournament {
+    pub config: TournamentConfig,
+    pub clock: Arc<Mutex<ScheduleClock>>,
+    state: Mutex<TournamentState>,
+}
+
+impl Tournament {
+    pub fn new(config: TournamentConfig) -> Tournament {
+        let clock = Arc::new(Mutex::new(ScheduleClock::new(config.schedule.clone())));
+        Tournament {
+            config,
+            clock,
+            state: Mutex::new(TournamentState {
+                registered: Vec::new(),
+                started: false,
+                tables: BTreeSet::new(),
+                last_chips: HashMap::new(),
+                standings: Vec::new(),
+                finished: false,
+            }),
+        }
+    }
+
+    pub fn register(&self, player_id: PlayerId) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.started {
+            return Err("The tournament has already started".to_string());
+        }
+        if state.registered.contains(&player_id) {
+            return Err(format!("{} is already registered", player_id));
+        }
+        state.registered.push(player_id);
+        Ok(())
+    }
+
+    // Seats every registered player, using create_table to make a table for each group
+    pub fn start(&self, mut create_table: impl FnMut(&[PlayerId]) -> TableId) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.started {
+            return Err("The tournament has already started".to_string());
+        }
+        if state.registered.len() < 2 {
+            return Err("At least two players must register".to_string());
+        }
+        state.started = true;
+        for group in initial_seating(&state.registered, self.config.table_config.max_players) {
+            let table_id = create_table(&group);
+            state.tables.insert(table_id);
+        }
+        let starting_chips = self.config.table_config.starting_chips;
+        state.last_chips = state.registered.iter().map(|p| (p.clone(), starting_chips)).collect();
+        Ok(())
+    }
+
+    pub fn payouts(&self) -> Vec<Chips> {
+        let state = self.state.lock().unwrap();
+        self.config.prizes.payouts(state.registered.len() as Chips * self.config.table_config.starting_chips)
+    }
+
+    pub fn is_playing(&self, table_id: TableId) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.finished && state.tables.contains(&table_id)
+    }
+
+    // Knocks out busted players, then moves players to balance and break tables. Call it between
+    // hands at any table. Returns (player, new table) for each player moved
+    pub fn after_hand(&self, tables: &BTreeMap<TableId, &Table>) -> Vec<(PlayerId, TableId)> {
+        let mut state = self.state.lock().unwrap();
+        if state.finished {
+            return Vec::new();
+        }
+        let tables: BTreeMap<TableId, &Table> = tables.iter().filter(|(t, _)| state.tables.contains(t)).map(|(&t, &table)| (t, table)).collect();
+        let prize_pool = state.registered.len() as Chips * self.config.table_config.starting_chips;
+        let payouts = self.config.prizes.payouts(prize_pool);
+
+        let mut busted: Vec<(Chips, PlayerId)> = Vec::new();
+        let mut busted_at: HashMap<PlayerId, TableId> = HashMap::new();
+        let mut remaining = 0;
+        for (&table_id, table) in &tables {
+            // Players who can't be taken off their table yet are placed once they can
+            let movable = table.movable_players();
+            for (player_id, chips) in table.chips() {
+                if chips > 0 {
+                    remaining += 1;
+                    state.last_chips.insert(player_id, chips);
+                } else if movable.contains(&player_id) {
+                    busted.push((state.last_chips.get(&player_id).copied().unwrap_or(0), player_id.clone()));
+                    busted_at.insert(player_id, table_id);
+                } else {
+                    remaining += 1;
+                }
+            }
+        }
+        for (place, player_id) in finishing_places(busted, remaining) {
+            match tables[&busted_at[&player_id]].take_player(&player_id, TableEvent::PlayerBusted{player_id: player_id.clone(), place}) {
+                Ok(_) => state.standings.insert(0, Standing{place, player_id, payout: payouts.get(place - 1).copied().unwrap_or(0)}),
+                Err(e) => println!("Couldn't knock out {}: {:?}", player_id, e),
+            }
+        }
+
+        if remaining <= 1 {
+            if let Some((player_id, table)) = tables.values().flat_map(|table| table.chips().into_keys().map(move |p| (p, table))).next() {
+                state.standings.insert(0, Standing{place: 1, player_id, payout: payouts.first().copied().unwrap_or(0)});
+                table.log_event(TableEvent::TournamentFinished{standings: state.standings.clone()});
+                table.stop();
+            }
+            state.finished = true;
+            return Vec::new();
+        }
+
+        let counts: BTreeMap<TableId, usize> = tables.iter().map(|(&t, table)| (t, table.chips().len())).collect();
+        let mut movable: BTreeMap<TableId, Vec<PlayerId>> = tables.iter().map(|(&t, table)| (t, table.movable_players())).collect();
+        let mut retval = Vec::new();
+        for (from, to) in plan_moves(&counts, self.config.table_config.max_players) {
+            let player_id = match movable.get_mut(&from).filter(|m| !m.is_empty()) {
+                Some(players) => players.remove(0),
+                None => continue,
+            };
+            if let Ok(player) = tables[&from].take_player(&player_id, TableEvent::PlayerMoved{player_id: player_id.clone(), to_table: to}) {
+                match tables[&to].seat_player(player) {
+                    Ok(()) => retval.push((player_id, to)),
+                    Err(_) => println!("Couldn't move {} to table {}", player_id, to),
+                }
+            }
+        }
+        for (&table_id, table) in &tables {
+            if table.chips().is_empty() {
+                state.tables.remove(&table_id);
+            }
+        }
+        retval
+    }
+
+    pub fn viewstate(&self, seating: HashMap<PlayerId, TableId>) -> TournamentViewState {
+        let state = self.state.lock().unwrap();
+        TournamentViewState {
+            registered: state.registered.clone(),
+            started: state.started,
+            finished: state.finished,
+            seating,
+            standings: state.standings.clone(),
+        }
+    }
+}
+
+mod test {
+    use crate::tournament::*;
+    use crate::bot::*;
+    use crate::bot_always_call::*;
+    use crate::static_config::*;
+
+    #[test]
+    fn test_payouts() {
+        let prizes = PrizeStructure{percentages: vec![50, 30, 20]};
+        assert!(prizes.is_valid());
+        assert_eq!(prizes.payouts(1000), vec![500, 300, 200]);
+        // 33 each, and first gets the leftover chip
+        assert_eq!(PrizeStructure{percentages: vec![34, 33, 33]}.payouts(100), vec![34, 33, 33]);
+        assert_eq!(PrizeStructure{percentages: vec![50, 30, 20]}.payouts(99), vec![51, 29, 19]);
+        assert!(!PrizeStructure{percentages: vec![50, 30]}.is_valid());
+    }
+
+    #[test]
+    fn test_finishing_places() {
+        let busted = vec![(300, "a".to_string()), (100, "b".to_string()), (200, "c".to_string())];
+        assert_eq!(finishing_places(busted, 2), vec![(5, "b".to_string()), (4, "c".to_string()), (3, "a".to_string())]);
+        assert_eq!(finishing_places(Vec::new(), 2), vec![]);
+    }
+
+    #[test]
+    fn test_initial_seating() {
+        let players: Vec<PlayerId> = (0..20).map(|p| p.to_string()).collect();
+        let seating = initial_seating(&players, 9);
+        assert_eq!(seating.iter().map(|t| t.len()).collect::<Vec<usize>>(), vec![7, 7, 6]);
+    }
+
+    #[test]
+    fn test_plan_moves() {
+        let counts = |c: Vec<usize>| -> BTreeMap<TableId, usize> { c.into_iter().enumerate().collect() };
+        // Balanced already
+        assert_eq!(plan_moves(&counts(vec![6, 5, 6]), 6), vec![]);
+        // Move from the biggest to the smallest
+        assert_eq!(plan_moves(&counts(vec![9, 6, 8]), 9), vec![(0, 1)]);
+        // 15 players fit at 2 tables, so the smallest table breaks
+        assert_eq!(plan_moves(&counts(vec![6, 5, 4]), 9), vec![(2, 1), (2, 0), (2, 1), (2, 0)]);
+        // Down to the final table
+        assert_eq!(plan_moves(&counts(vec![5, 4]), 9), vec![(1, 0), (1, 0), (1, 0), (1, 0)]);
+    }
+
+    fn tournament_table(players: &[(&str, Chips)]) -> Table {
+        let config = tournament_config();
+        let static_config = StaticConfig {
+            serve_address: String::new(),
+            serve_port: 0,
+            tls: false,
+            cert_path: String::new(),
+            key_path: String::new(),
+            ms_between_rounds: 0,
+            variants_dir: None,
+        };
+        let rules = TableRules {
+            ante: AnteRule::Blinds(vec![Blind{amount: 1}, Blind{amount: 2}]),
+            ante_name: "Blind".to_string(),
+            min_bet: 2,
+            betting: BettingStructure::NoLimit,
+            rake: None,
+            straddle: None,
+            bomb_pot: None,
+            run_it: None,
+            all_in_pause_ms: 0,
+        };
+        let table = Table::with_static_config(config.table_config, rules.clone(), Box::new(move |_, _| rules.ante.clone()), static_config);
+        table.set_tournament();
+        for &(player_id, chips) in players {
+            let input = Arc::new(BotInputSource::new(Arc::new(BotAlwaysCall::new())));
+            assert!(table.seat_player(LivePlayer{player_id: player_id.to_string(), chips, input}).is_ok());
+        }
+        table
+    }
+
+    fn tournament_config() -> TournamentConfig {
+        TournamentConfig {
+            table_config: TableConfig {
+                max_players: 4,
+                starting_chips: 100,
+                variant_selector: PokerVariantSelector::Rotation(PokerVariants{descs: vec![PokerVariantDesc{name: "Texas Hold 'Em".to_string(), special_cards: Vec::new()}]}),
+                timer: ActionTimer::default(),
+                rebuys: RebuyRules::default(),
+                sit_and_go: None,
+            },
+            schedule: BlindSchedule{entries: Vec::new()},
+            betting: BettingStructure::NoLimit,
+            prizes: PrizeStructure{percentages: vec![50, 30, 20]},
+        }
+    }
+
+    #[test]
+    fn test_break_table_down_to_one() {
+        let tournament = Tournament::new(tournament_config());
+        for player_id in ["a", "b", "c", "d", "e"] {
+            assert!(tournament.register(player_id.to_string()).is_ok());
+        }
+        let mut next_table = 0;
+        assert!(tournament.start(|_| {
+            next_table += 1;
+            next_table - 1
+        }).is_ok());
+        // Table 0 busts down to a single player
+        let tables = vec![
+            tournament_table(&[("a", 200), ("b", 0)]),
+            tournament_table(&[("c", 100), ("d", 100), ("e", 100)]),
+        ];
+        let table_refs: BTreeMap<TableId, &Table> = tables.iter().enumerate().collect();
+        assert_eq!(tournament.after_hand(&table_refs), vec![("a".to_string(), 1)]);
+        assert!(!tournament.is_playing(0));
+        assert!(tournament.is_playing(1));
+        assert!(tables[0].chips().is_empty());
+        assert_eq!(tables[1].chips().len(), 4);
+        assert_eq!(tournament.viewstate(HashMap::new()).standings, vec![Standing{place: 5, player_id: "b".to_string(), payout: 0}]);
+    }
+
+    #[test]
+    fn test_chips_stay_in_play() {
+        let table = tournament_table(&[("a", 100), ("b", 100)]);
+        assert_eq!(table.leave(&"a".to_string()), Err(SeatError::NotAllowed));
+        assert_eq!(table.chips().len(), 2);
+
+        let mut config = tournament_config();
+        config.schedule = BlindSchedule{entries: vec![ScheduleEntry::Level(BlindLevel{small_blind: 1, big_blind: 2, ante: 0, big_blind_ante: false, duration: LevelDuration::Minutes(10)})]};
+        assert!(config.is_valid());
+        config.table_config.rebuys.allowed = true;
+        assert!(!config.is_valid());
+        config.table_config.rebuys = RebuyRules{add_on: Some(AddOn{amount: 100, until_hand: 10}), ..RebuyRules::default()};
+        assert!(!config.is_valid());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::card::*;
+use crate::game::*;
+
+use serde::{Serialize, Deserialize};
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(from = "VariantFileFields")]
+pub struct VariantFile {
+    pub name: String,
+    #[serde(flatten)]
+    pub variant: PokerVariant,
+}
+
+// What a file may contain. Flattened fields can't reject unknown keys, so they're listed out here
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VariantFileFields {
+    name: String,
+    rules: Vec<Round>,
+    use_from_hand: usize,
+    #[serde(default)]
+    split_pot: SplitPot,
+    #[serde(default)]
+    ranking: HandRanking,
+}
+
+impl From<VariantFileFields> for VariantFile {
+    fn from(fields: VariantFileFields) -> VariantFile {
+        let VariantFileFields{name, rules, use_from_hand, split_pot, ranking} = fields;
+        VariantFile {
+            name,
+            variant: PokerVariant{rules, use_from_hand, split_pot, ranking},
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VariantFileError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl std::fmt::Display for VariantFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid variant file {}: {}", self.path.display(), self.reason)
+    }
+}
+
+impl VariantFile {
+    pub fn parse(path: &Path, contents: &str) -> Result<VariantFile, String> {
+        let file: VariantFile = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(contents).map_err(|e| e.to_string())?,
+            Some("toml") => toml::from_str(contents).map_err(|e| e.to_string())?,
+            _ => return Err("Unknown extension, expected .json or .toml".to_string()),
+        };
+        file.validate()?;
+        Ok(file)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Variant name is empty".to_string());
+        }
+        let PokerVariant{rules, use_from_hand, ..} = &self.variant;
+        let mut hand_size = 0;
+        let mut has_bet = false;
+        for (idx, round) in rules.iter().enumerate() {
+            match round {
+                Round::Ante if idx != 0 => return Err(format!("Round {} is an Ante, but only the first round may be", idx)),
+                Round::DrawToHand{facing} if facing.is_empty() => return Err(format!("Round {} draws no cards", idx)),
+                Round::DrawToHand{facing} => hand_size += facing.len(),
+                Round::DrawToCommunity{quant: 0} => return Err(format!("Round {} draws no cards", idx)),
+                Round::Bet{..} => has_bet = true,
+                Round::Replace{rule, max_possible_replace, extra_replace, ..} => {
+                    let max = match rule {
+                        ReplaceRule::Fixed(max) => *max,
+                        ReplaceRule::HoldingRank{rank, max, with_rank} => {
+                            if *rank >= NUM_RANKS {
+                                return Err(format!("Round {} has invalid rank {}", idx, rank));
+                            }
+                            std::cmp::max(*max, *with_rank)
+                        }
+                    };
+                    if max > hand_size {
+                        return Err(format!("Round {} replaces up to {} cards, but the hand only has {}", idx, max, hand_size));
+                    }
+                    if max > std::cmp::max(*max_possible_replace, *extra_replace) {
+                        return Err(format!("Round {} replaces up to {} cards, more than max_possible_replace and extra_replace allow", idx, max));
+                    }
+                },
+                _ => {}
+            }
+        }
+        if rules.first() != Some(&Round::Ante) {
+            return Err("The first round must be an Ante".to_string());
+        }
+        if hand_size == 0 {
+            return Err("No round deals cards to players".to_string());
+        }
+        if !has_bet {
+            return Err("No betting round".to_string());
+        }
+        if *use_from_hand == 0 || *use_from_hand > std::cmp::min(hand_size, 5) {
+            return Err(format!("use_from_hand must be between 1 and {}", std::cmp::min(hand_size, 5)));
+        }
+        if variant_max_players(&self.variant, NUM_RANKS * NUM_SUITS) < 2 {
+            return Err("Not enough cards in the deck for two players".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Returns every file in dir, or the errors for the ones that couldn't be loaded
+pub fn load_variant_dir(dir: &Path) -> (Vec<VariantFile>, Vec<VariantFileError>) {
+    let mut loaded = Vec::new();
+    let mut errors = Vec::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            errors.push(VariantFileError{path: dir.to_path_buf(), reason: e.to_string()});
+            return (loaded, errors);
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_file()).collect();
+    paths.sort();
+    let mut names = HashSet::new();
+    for path in paths {
+        let result = std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|contents| {
+            let file = VariantFile::parse(&path, &contents)?;
+            if PokerVariants::contains(&file.name) || !names.insert(file.name.clone()) {
+                return Err(format!("A variant named \"{}\" already exists", file.name));
+            }
+            Ok(file)
+        });
+        match result {
+            Ok(file) => loaded.push(file),
+            Err(reason) => errors.push(VariantFileError{path, reason}),
+        }
+    }
+    (loaded, errors)
+}
+
+// Loads and registers every valid variant file in dir, returning the errors for the rest
+pub fn register_variant_dir(dir: &Path) -> Vec<VariantFileError> {
+    let (loaded, errors) = load_variant_dir(dir);
+    for file in loaded {
+        println!("Loaded variant \"{}\"", file.name);
+        PokerVariants::register(file.name, file.variant);
+    }
+    errors
+}
+
+mod test {
+    use crate::variant_file::*;
+
+    #[test]
+    fn parse_toml() {
+        let contents = r#"
+name = "Test Draw"
+use_from_hand = 5
+rules = [
+    {kind = "Ante"},
+    {kind = "DrawToHand", data = {facing = [{kind = "FaceDown"}, {kind = "FaceDown"}, {kind = "FaceDown"}, {kind = "FaceDown"}, {kind = "FaceDown"}]}},
+    {kind = "Bet", data = {order = {kind = "Position"}}},
+    {kind = "Replace", data = {rule = {kind = "HoldingRank", data = {rank = 0, max = 3, with_rank = 4}}, max_possible_replace = 3, extra_replace = 4}},
+    {kind = "Bet", data = {order = {kind = "Position"}}},
+]
+"#;
+        let file = VariantFile::parse(Path::new("test.toml"), contents).unwrap();
+        assert_eq!(file.name, "Test Draw");
+        assert_eq!(file.variant, five_card_draw());
+    }
+
+    #[test]
+    fn parse_json_round_trip() {
+        let file = VariantFile {
+            name: "Hold 'Em Copy".to_string(),
+            variant: texas_hold_em(),
+        };
+        let contents = serde_json::to_string(&file).unwrap();
+        assert_eq!(VariantFile::parse(Path::new("copy.json"), &contents), Ok(file));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let contents = r#"{"name": "No Cards", "use_from_hand": 2, "rules": [{"kind": "Ante"}, {"kind": "Bet", "data": {}}]}"#;
+        assert_eq!(VariantFile::parse(Path::new("bad.json"), contents), Err("No round deals cards to players".to_string()));
+        assert!(VariantFile::parse(Path::new("bad.json"), "{\"name\": 5}").is_err());
+        assert!(VariantFile::parse(Path::new("bad.yaml"), "").is_err());
+    }
+
+    #[test]
+    fn parse_unknown_fields() {
+        let parse = |contents: &str| VariantFile::parse(Path::new("old.json"), contents).unwrap_err();
+        let rules = r#"[{"kind": "Ante"}, {"kind": "DrawToHand", "data": {"facing": [{"kind": "FaceDown"}]}}, {"kind": "Bet", "data": {}}]"#;
+        let error = parse(&format!(r#"{{"name": "Old", "use_from_hand": 1, "starting_player": 0, "rules": {}}}"#, rules));
+        assert!(error.contains("unknown field `starting_player`"), "{}", error);
+        // Keys inside a round are checked too
+        let error = parse(r#"{"name": "Typo", "use_from_hand": 1, "rules": [{"kind": "Ante"}, {"kind": "DrawToHand", "data": {"facing": [{"kind": "FaceDown"}]}}, {"kind": "Bet", "data": {"opener": null}}]}"#);
+        assert!(error.contains("unknown field `opener`"), "{}", error);
+        let error = VariantFile::parse(Path::new("typo.toml"), "name = \"Typo\"\nuse_from_hand = 1\nsplitpot = {kind = \"HighOnly\"}\nrules = []").unwrap_err();
+        assert!(error.contains("unknown field `splitpot`"), "{}", error);
+    }
+
+    #[test]
+    fn load_example_dir() {
+        let (loaded, errors) = load_variant_dir(Path::new("variants"));
+        assert_eq!(errors, Vec::new());
+        assert!(loaded.iter().any(|f| f.name == "Pineapple"));
+    }
+}
@@ -66,16 +66,32 @@ impl<C: Crypto> DeckDealer for VerifiedDeckDealer<C> {
 pub struct VerifiedDeckClient<'a, R: Rng, C: Crypto> {
     dealer: Mutex<Box<dyn DeckDealer>>,
     crypto: C,
-    rng: &'a mut R
+    rng: &'a mut R,
+    // Reshuffled discards, dealt once the dealer's cards run out. The client saw these cards
+    // when they were thrown away, so they're picked here instead of going back through the dealer
+    discards: Vec<Card>,
 }
 
 impl<'a, R: Rng, C: Crypto> Deck for VerifiedDeckClient<'a, R, C> {
     fn draw(&mut self) -> Result<Card, PokerRoundError> {
         let mut d = self.dealer.lock().unwrap();
         let deck = d.request_deck();
+        if deck.is_empty() {
+            let idx = (0..self.discards.len()).choose(self.rng).ok_or_else(|| "Ran out of cards in deck".to_string())?;
+            return Ok(self.discards.swap_remove(idx));
+        }
         let (idx, picked) = deck.into_iter().enumerate().choose(self.rng).unwrap();
         let enc_picked = Encrypted::encrypt(&picked.ciphertext, &self.crypto);
         let dec_picked: Vec<u8> = d.decrypt_card(&enc_picked, idx).as_t();
         Ok(serde_json::from_slice(&self.crypto.decrypt(&dec_picked)).unwrap())
     }
+
+    fn cards_left(&self) -> usize {
+        self.dealer.lock().unwrap().request_deck().len() + self.discards.len()
+    }
+
+    fn reshuffle(&mut self, discards: Vec<Card>) -> Result<(), PokerRoundError> {
+        self.discards.extend(discards);
+        Ok(())
+    }
 }
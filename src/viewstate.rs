@@ -3,6 +3,8 @@ use crate::game::*;
 use crate::gamestate::*;
 use crate::special_card::*;
 use crate::table::*;
+use crate::blind_schedule::*;
+use crate::tournament::*;
 use crate::gamestate;
 
 use ts_rs::{TS, export};
@@ -53,6 +55,8 @@ pub struct PlayerViewState {
 #[derive(TS)]
 pub struct PokerVariantViewState {
     pub use_from_hand: usize,
+    #[serde(default)]
+    pub ranking: HandRanking,
 }
 
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
@@ -63,6 +67,9 @@ pub struct PokerViewState {
     pub community_cards: Vec<CardViewState>,
     pub bet_this_round: HashMap<PlayerRole, Chips>,
     pub current_turn: Option<PlayerRole>,
+    // Left in the pot by a passed out hand
+    #[serde(default)]
+    pub carried_pot: Chips,
 
     #[serde(skip_serializing)]
     pub rules: SpecialRules,
@@ -79,6 +86,11 @@ pub struct TableViewState {
     pub seats: HashMap<PlayerId, Seat>,
     pub config: TableConfig,
     pub running_variant: Option<PokerVariantDesc>,
+    #[serde(default)]
+    pub sitting_out: Vec<PlayerId>,
+    // The current level when the table follows a blind schedule
+    #[serde(default)]
+    pub level: Option<LevelViewState>,
 }
 
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
@@ -86,6 +98,7 @@ pub struct TableViewState {
 #[serde(tag = "kind", content="data")]
 pub enum BetDiffKind {
     Blind(String),
+    BringIn,
     Check,
     Call,
     Raise {
@@ -105,6 +118,12 @@ pub enum PokerViewDiff<P> {
     CommunityDraw {
         drawn: Vec<CardViewState>
     },
+    // One of several boards dealt when the players all in agree to run it more than once
+    Run {
+        run: usize,
+        times: usize,
+        drawn: Vec<CardViewState>
+    },
     Fold {
         player: P,
     },
@@ -124,7 +143,24 @@ pub enum PokerViewDiff<P> {
     ShowCards {
         player: P,
         shown: Vec<(usize, CardViewState)>,
-        strength: HandStrength
+        strength: HandStrength,
+        low: Option<HandStrength>,
+    },
+    Muck {
+        player: P
+    },
+    StandPat {
+        player: P
+    },
+    Reshuffle {
+        cards: usize
+    },
+    Openers {
+        player: P,
+        strength: HandStrength,
+    },
+    PassedOut {
+        carried: Chips
     },
     Winners(Winners<P>),
     Unknown
@@ -143,6 +179,55 @@ pub enum TableEvent {
     AnteChange {
         new_table_rules: TableRules
     },
+    HandAborted {
+        reason: String
+    },
+    PlayerSatOut {
+        player_id: PlayerId
+    },
+    PlayerSatIn {
+        player_id: PlayerId
+    },
+    PlayerLeft {
+        player_id: PlayerId,
+        chips: Chips
+    },
+    BuyIn {
+        player_id: PlayerId,
+        kind: BuyInKind,
+        chips: Chips
+    },
+    BreakStarted {
+        minutes: u32
+    },
+    PlayerMoved {
+        player_id: PlayerId,
+        to_table: usize
+    },
+    PlayerBusted {
+        player_id: PlayerId,
+        place: usize
+    },
+    TournamentFinished {
+        standings: Vec<Standing>
+    },
+    Straddle {
+        player_id: PlayerId,
+        chips: Chips
+    },
+    BombPotVote {
+        player_id: PlayerId
+    },
+    BombPot {
+        ante: Chips
+    },
+    NoVariantFits {
+        players: usize
+    },
+    CarriedPotRefunded {
+        player_id: PlayerId,
+        chips: Chips
+    },
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -186,6 +271,9 @@ impl<P: std::fmt::Display> std::fmt::Display for PokerViewDiff<P> {
             CommunityDraw {drawn} => {
                 write!(f, "{} drawn to community", drawn.iter().map(|cvs| cvs.to_string()).collect::<Vec<String>>().join(", "))?;
             },
+            Run {run, times, drawn} => {
+                write!(f, "Run {} of {}: {} drawn to community", run, times, drawn.iter().map(|cvs| cvs.to_string()).collect::<Vec<String>>().join(", "))?;
+            },
             Fold {player} => {
                 write!(f, "{} folded :(", player)?;
             },
@@ -197,6 +285,7 @@ impl<P: std::fmt::Display> std::fmt::Display for PokerViewDiff<P> {
                 use BetDiffKind::*;
                 match bet_kind {
                     Blind(name) => {write!(f, "{} {}", name, chips)?;},
+                    BringIn => { write!(f, "brought it in for {}", chips)?; },
                     Check => { write!(f, "checked")?; },
                     Call => { write!(f, "called {}", chips)?; },
                     Raise{diff_from_last_raise, ..} => { write!(f, "raised {}", diff_from_last_raise)?; }
@@ -210,15 +299,49 @@ impl<P: std::fmt::Display> std::fmt::Display for PokerViewDiff<P> {
                     write!(f, "{} replaced {} cards", player, drawn.len());
                 }
             },
-            ShowCards{player, shown, strength} => {
+            ShowCards{player, shown, strength, low} => {
                 write!(f, "{} shows {} to give them a {}", player, shown.iter().map(|(_, cvs)| cvs.to_string()).collect::<Vec<String>>().join(", "), strength)?;
+                if let Some(low) = low {
+                    write!(f, " and a {}", low)?;
+                }
+            },
+            Muck{player} => {
+                write!(f, "{} mucked", player)?;
+            },
+            StandPat{player} => {
+                write!(f, "{} stands pat", player)?;
+            },
+            Reshuffle{cards} => {
+                write!(f, "The deck ran low, so {} discards were shuffled back in", cards)?;
+            },
+            Openers{player, strength} => {
+                write!(f, "{} opened with a {}", player, strength)?;
+            },
+            PassedOut{carried} => {
+                write!(f, "Nobody could open. {} carries over to the next hand", carried)?;
             },
             PokerViewDiff::Winners(gamestate::Winners{winners_by_pot}) => {
-                for (Subpot{chips, ..}, winners) in winners_by_pot {
+                let write_winners = |f: &mut std::fmt::Formatter, winners: &Vec<P>, chips: Chips, half: &str| {
                     if winners.len() == 1 {
-                        write!(f, "{} wins {}", winners.first().unwrap(), chips)?;
+                        write!(f, "{} wins {}{}", winners.first().unwrap(), half, chips)
                     } else {
-                        write!(f, "{} split {}", winners.iter().map(|w| w.to_string()).collect::<Vec<String>>().join(", "), chips)?;
+                        write!(f, "{} split {}{}", winners.iter().map(|w| w.to_string()).collect::<Vec<String>>().join(", "), half, chips)
+                    }
+                };
+                for (idx, pot) in winners_by_pot.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    let (high, low) = pot.halves();
+                    if pot.low.is_empty() {
+                        write_winners(f, &pot.high, high, "")?;
+                    } else {
+                        write_winners(f, &pot.high, high, "the high half, ")?;
+                        write!(f, ", ")?;
+                        write_winners(f, &pot.low, low, "the low half, ")?;
+                    }
+                    if pot.rake > 0 {
+                        write!(f, " after a rake of {}", pot.rake)?;
                     }
                 }
             },
@@ -287,11 +410,17 @@ impl<P: Clone + Eq + Hash> PokerViewDiff<P> {
         match self {
             Draw{player, drawn} => Draw{player: mapping.get(player).cloned().unwrap(), drawn: drawn.clone()},
             CommunityDraw{drawn} => CommunityDraw{drawn: drawn.clone()},
+            Run{run, times, drawn} => Run{run: *run, times: *times, drawn: drawn.clone()},
             Fold{player} => Fold{player: mapping.get(player).cloned().unwrap()},
             TurnStart{player} => TurnStart{player: mapping.get(player).cloned().unwrap()},
             Bet{bet_kind, player, chips} => Bet{bet_kind: bet_kind.clone(), player: mapping.get(player).cloned().unwrap(), chips: *chips},
             Replace{player, discard, drawn} => Replace{player: mapping.get(player).cloned().unwrap(), discard: discard.clone(), drawn: drawn.clone()},
-            ShowCards{player, shown, strength} => ShowCards{player: mapping.get(player).cloned().unwrap(), shown: shown.clone(), strength: strength.clone()},
+            ShowCards{player, shown, strength, low} => ShowCards{player: mapping.get(player).cloned().unwrap(), shown: shown.clone(), strength: strength.clone(), low: low.clone()},
+            Muck{player} => Muck{player: mapping.get(player).cloned().unwrap()},
+            StandPat{player} => StandPat{player: mapping.get(player).cloned().unwrap()},
+            Reshuffle{cards} => Reshuffle{cards: *cards},
+            Openers{player, strength} => Openers{player: mapping.get(player).cloned().unwrap(), strength: *strength},
+            PassedOut{carried} => PassedOut{carried: *carried},
             Winners(ws) => Winners(ws.convert(mapping)),
             Unknown => Unknown,
         }
@@ -368,15 +497,21 @@ impl PokerViewState {
             community_cards,
             bet_this_round,
             current_turn,
+            carried_pot: state.carried_pot,
             rules: rules.clone(),
             variant: PokerVariantViewState {
-                use_from_hand: variant.use_from_hand
+                use_from_hand: variant.use_from_hand,
+                ranking: variant.ranking,
             },
         }
     }
 
-    pub fn valid_bet(&self, min_bet: Chips, call_amount: Chips, bet: Chips, role: PlayerRole) -> Result<(), BetInvalidError> {
-        // Check all-ins. All-ins are always valid (until max-bets are implemented)
+    pub fn valid_bet(&self, min_bet: Chips, max_bet: Chips, call_amount: Chips, bet: Chips, role: PlayerRole) -> Result<(), BetInvalidError> {
+        // Check the betting structure's limit. Calling is always allowed
+        if bet != call_amount && bet > max_bet {
+            return Err("Bet more than maximum".to_string());
+        }
+        // Check all-ins. All-ins are always valid if under the max
         let bettable = self.bettable_chips(role);
         if bet == bettable {
             return Ok(());
@@ -420,7 +555,7 @@ impl PokerViewState {
     }
 
     pub fn pot(&self) -> Chips {
-        self.bet_this_round.values().copied().sum::<Chips>() + self.players.values().map(|p| p.total_bet).sum::<Chips>()
+        self.carried_pot + self.bet_this_round.values().copied().sum::<Chips>() + self.players.values().map(|p| p.total_bet).sum::<Chips>()
     }
 }
 
@@ -442,8 +577,69 @@ impl std::fmt::Display for TableEvent {
                 match &new_table_rules.ante {
                     Ante(ante) => write!(f, "Ante is now {}", ante)?,
                     Blinds(blinds) => write!(f, "Blinds are now {}", blinds.iter().map(|b| b.amount.to_string()).collect::<Vec<String>>().join(", "))?,
+                    BlindsAnte{blinds, ante, big_blind_ante} => {
+                        write!(f, "Blinds are now {}", blinds.iter().map(|b| b.amount.to_string()).collect::<Vec<String>>().join(", "))?;
+                        if *big_blind_ante {
+                            write!(f, " with a big blind ante of {}", ante)?;
+                        } else {
+                            write!(f, " with an ante of {}", ante)?;
+                        }
+                    },
+                    BringIn{ante, bring_in} => write!(f, "Ante is now {}, bring-in {}", ante, bring_in)?,
+                }
+            },
+            HandAborted {reason} => {
+                write!(f, "Hand aborted ({}). All chips were returned", reason)?;
+            },
+            PlayerSatOut {player_id} => {
+                write!(f, "{} is sitting out", player_id)?;
+            },
+            PlayerSatIn {player_id} => {
+                write!(f, "{} is back", player_id)?;
+            },
+            PlayerLeft {player_id, chips} => {
+                write!(f, "{} left the table with {} chips", player_id, chips)?;
+            },
+            BuyIn {player_id, kind, chips} => {
+                match kind {
+                    BuyInKind::BuyIn => write!(f, "{} bought in for {}", player_id, chips)?,
+                    BuyInKind::Rebuy => write!(f, "{} rebought for {}", player_id, chips)?,
+                    BuyInKind::AddOn => write!(f, "{} took the add-on for {}", player_id, chips)?,
                 }
             },
+            BreakStarted {minutes} => {
+                write!(f, "Break for {} minutes", minutes)?;
+            },
+            PlayerMoved {player_id, to_table} => {
+                write!(f, "{} moved to table {}", player_id, to_table)?;
+            },
+            PlayerBusted {player_id, place} => {
+                write!(f, "{} finished in place {}", player_id, place)?;
+            },
+            TournamentFinished {standings} => {
+                write!(f, "Tournament over")?;
+                for Standing{place, player_id, payout} in standings {
+                    write!(f, "\n{}. {}", place, player_id)?;
+                    if *payout > 0 {
+                        write!(f, " wins {}", payout)?;
+                    }
+                }
+            },
+            Straddle {player_id, chips} => {
+                write!(f, "{} straddled for {}", player_id, chips)?;
+            },
+            BombPotVote {player_id} => {
+                write!(f, "{} voted for a bomb pot", player_id)?;
+            },
+            BombPot {ante} => {
+                write!(f, "Bomb pot! Everyone antes {}", ante)?;
+            },
+            NoVariantFits {players} => {
+                write!(f, "No game can be dealt to {} players. The table has been stopped", players)?;
+            },
+            CarriedPotRefunded {player_id, chips} => {
+                write!(f, "{} gets back {} they put into a passed out pot", player_id, chips)?;
+            },
         }
         Ok(())
     }
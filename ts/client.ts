@@ -15,6 +15,20 @@ import {
     AnteRuleChangeDesc,
     ServerTableParameters,
     SpecialCardGroupDesc,
+    BettingStructure,
+    ActionClock,
+    RebuyRules,
+    BlindSchedule,
+    ScheduleEntry,
+    LevelDuration,
+    LevelViewState,
+    TournamentConfig,
+    TournamentViewState,
+    PrizeStructure,
+    Rake,
+    StraddleRule,
+    BombPotRule,
+    ShowResp,
 } from "./pokerrs.ts";
 
 var auth_token: string | null = null;
@@ -72,6 +86,13 @@ function fetch_update(player_id: string | null, start_from: number, known_action
                 set_auth_token_cookie();
             }
             draw(player_id, update);
+            // Tournaments move players between tables
+            const table_id = update.player?.table_id ?? null;
+            if (table_id !== null && `${table_id}` != current_table_id) {
+                current_table_id = `${table_id}`;
+                start_from = 0;
+                action_requested = null;
+            }
             fetch_update(player_id, start_from, action_requested);
         } else {
             name_screen.classList.remove("hidden");
@@ -83,7 +104,14 @@ function fetch_update(player_id: string | null, start_from: number, known_action
 
 var clicked_cards: Array<number> | null = null;
 var max_can_replace: number = 0;
+// The endpoint to answer when the player was asked a yes or no question, like whether to straddle
+var question_requested: string | null = null;
+var show_uncontested_requested: boolean = false;
 function clicked_card(cidx: number) {
+    if (show_uncontested_requested) {
+        show_uncontested({kind: "One", data: cidx});
+        return;
+    }
     const call_button = <HTMLInputElement>document.getElementById("call_button")!;
     const player_screen = document.getElementById("player_div")!;
     const player_cards = player_screen.getElementsByClassName("card_container")[0]!.children;
@@ -197,7 +225,7 @@ function make_table_player_screen(idx: number, player_id: string, table: TableVi
         poker_button_span.innerHTML = "SB";
         poker_button_span.classList.add("small_blind_button");
     }
-    player_label.innerHTML = player_id;
+    player_label.innerHTML = table.sitting_out.includes(player_id) ? `${player_id} (sitting out)` : player_id;
     player_chips_label.innerHTML = "";
     player_cards.innerHTML = "";
     return player_screen;
@@ -370,6 +398,9 @@ function draw_action(action: ServerActionRequest | null, viewstate: PokerViewSta
     const dealers_choice_list = <HTMLElement>document.getElementById("dealers_choice_list")!;
 
     call_button.value = "Call";
+    fold_button.value = "Fold";
+    question_requested = null;
+    show_uncontested_requested = false;
     dealers_choice_list.innerHTML = "";
     dealers_choice_modal.classList.add("hidden");
 
@@ -390,12 +421,17 @@ function draw_action(action: ServerActionRequest | null, viewstate: PokerViewSta
         } else {
             call_button.value = "Check";
         }
+        let max = Math.min(bettable, action.data.max_bet - bet_this_round);
         let min = action.data.min_bet - bet_this_round;
-        if (bettable < min) {
-            min = bettable;
+        if (max < min) {
+            min = max;
+        }
+        if (action.data.max_bet <= call_amount) {
+            bet_button.setAttribute("disabled", "");
+            bet_input.setAttribute("disabled", "");
         }
         bet_input.setAttribute("min", min.toString());
-        bet_input.setAttribute("max", bettable.toString());
+        bet_input.setAttribute("max", max.toString());
         bet_input.value = min.toString();
         call_amount_input.value = call_amount.toString();
         bet_this_round_input.value = bet_this_round.toString();
@@ -413,6 +449,51 @@ function draw_action(action: ServerActionRequest | null, viewstate: PokerViewSta
         max_can_replace = action.data.max_can_replace;
 
         clicked_cards = [];
+    } else if (action && action.kind == "Straddle") {
+        bet_button.setAttribute("disabled", "");
+        bet_input.setAttribute("disabled", "");
+        replace_cards_label.classList.add("hidden");
+        bet_input.value = "";
+
+        call_button.removeAttribute("disabled");
+        fold_button.removeAttribute("disabled");
+        call_button.value = `Straddle ${action.data.amount}`;
+        fold_button.value = "No Straddle";
+        question_requested = "straddle";
+    } else if (action && action.kind == "RunIt") {
+        bet_button.setAttribute("disabled", "");
+        bet_input.setAttribute("disabled", "");
+        replace_cards_label.classList.add("hidden");
+        bet_input.value = "";
+
+        call_button.removeAttribute("disabled");
+        fold_button.removeAttribute("disabled");
+        call_button.value = `Run It ${action.data.times} Times`;
+        fold_button.value = "Run It Once";
+        question_requested = "run_it";
+    } else if (action && action.kind == "ShowOrMuck") {
+        bet_button.setAttribute("disabled", "");
+        bet_input.setAttribute("disabled", "");
+        replace_cards_label.classList.add("hidden");
+        bet_input.value = "";
+
+        call_button.removeAttribute("disabled");
+        fold_button.removeAttribute("disabled");
+        call_button.value = "Show";
+        fold_button.value = "Muck";
+        question_requested = "show_or_muck";
+    } else if (action && action.kind == "ShowUncontested") {
+        bet_button.setAttribute("disabled", "");
+        bet_input.setAttribute("disabled", "");
+        bet_input.value = "";
+
+        call_button.removeAttribute("disabled");
+        fold_button.removeAttribute("disabled");
+        call_button.value = "Show All";
+        fold_button.value = "Muck";
+        replace_cards_label.classList.remove("hidden");
+        replace_cards_label.innerHTML = "You won. Click a card to show just that one";
+        show_uncontested_requested = true;
     } else if (action && action.kind == "DealersChoice") {
         call_button.setAttribute("disabled", "");
         fold_button.setAttribute("disabled", "");
@@ -454,6 +535,70 @@ function draw_action(action: ServerActionRequest | null, viewstate: PokerViewSta
     }
 }
 
+var action_timer_interval: number | null = null;
+var action_timer_request: string | null = null;
+function draw_action_timer(action: ServerActionRequest | null) {
+    const action_timer_label = document.getElementById("action_timer_label")!;
+    // Only restart the countdown when a new action is requested
+    const request = JSON.stringify(action);
+    if (request == action_timer_request) {
+        return;
+    }
+    action_timer_request = request;
+    if (action_timer_interval != null) {
+        window.clearInterval(action_timer_interval);
+        action_timer_interval = null;
+    }
+    action_timer_label.innerHTML = "";
+    const timer: ActionClock | null = action?.data.timer ?? null;
+    if (!timer) {
+        return;
+    }
+    const started = Date.now();
+    const tick = () => {
+        const elapsed = Date.now() - started;
+        const action_left = Math.max(0, timer.action_ms - elapsed);
+        const bank_left = Math.max(0, timer.time_bank_ms - Math.max(0, elapsed - timer.action_ms));
+        if (action_left > 0) {
+            action_timer_label.innerHTML = `${Math.ceil(action_left / 1000)}s`;
+        } else {
+            action_timer_label.innerHTML = `Time bank ${Math.ceil(bank_left / 1000)}s`;
+        }
+    };
+    tick();
+    action_timer_interval = window.setInterval(tick, 250);
+}
+
+function draw_sit_in(sitting_out: boolean) {
+    const sit_in_button = document.getElementById("sit_in_button")!;
+    const sit_out_button = document.getElementById("sit_out_button")!;
+    if (sitting_out) {
+        sit_in_button.classList.remove("hidden");
+        sit_out_button.classList.add("hidden");
+    } else {
+        sit_in_button.classList.add("hidden");
+        sit_out_button.classList.remove("hidden");
+    }
+}
+
+// action is one of sit_in, sit_out, leave, rebuy or add_on
+function seat_action(action: string) {
+    const player_input = document.getElementById("name_input")!;
+    const player_id = (<HTMLInputElement>player_input).value.trim();
+    fetch(`/${action}?player=${player_id}&table_id=${current_table_id}`, {
+        method: "POST",
+        headers: auth_headers(),
+    }).then(resp => {
+        if (resp.ok && (action == "sit_in" || action == "sit_out")) {
+            draw_sit_in(action == "sit_out");
+        }
+        // Polling stops once the player is off the table, so let them join again
+        if (resp.ok && action == "leave") {
+            document.getElementById("name_screen")!.classList.remove("hidden");
+        }
+    });
+}
+
 var client_logs: Array<Array<String>> = [];
 var log_page: number = 0;
 var current_table_id = "";
@@ -512,6 +657,9 @@ function draw(player_id: string, update: ServerUpdate) {
     const action = update.player?.action_requested ?? null;
     draw_players(player_id, viewstate, update.table);
     draw_action(action, viewstate);
+    draw_action_timer(action);
+    draw_sit_in(update.player?.sitting_out ?? false);
+    draw_level(update.table.level ?? null);
 
     variant_label.innerHTML = update.table.running_variant?.name ?? "Waiting for next game...";
     if (update.table.running_variant) {
@@ -520,10 +668,17 @@ function draw(player_id: string, update: ServerUpdate) {
         }
     }
 
+    const stop_server_button = document.getElementById("stop_server_button")!;
+    const settlement_link = <HTMLAnchorElement>document.getElementById("settlement_link")!;
+    settlement_link.href = `/settlement?table_id=${current_table_id}&format=csv`;
     if (update.table.running) {
         start_server_button.classList.add("hidden");
+        stop_server_button.classList.remove("hidden");
+        settlement_link.classList.add("hidden");
     } else {
         start_server_button.classList.remove("hidden");
+        stop_server_button.classList.add("hidden");
+        settlement_link.classList.remove("hidden");
         if (Object.keys(update.table.seats).length > 1) {
             start_server_button.removeAttribute("disabled");
         } else {
@@ -534,7 +689,7 @@ function draw(player_id: string, update: ServerUpdate) {
     const pot_label = document.getElementById("pot_label")!;
     const community_cards = document.getElementById("community_cards")!;
     if (viewstate) {
-        let pot = 0;
+        let pot = Number(viewstate.carried_pot);
         for (const [_, player] of Object.entries(viewstate.players)) {
             pot += Number(player.total_bet);
         }
@@ -555,9 +710,57 @@ function draw(player_id: string, update: ServerUpdate) {
     update_logs(update, null);
 }
 
+var current_tournament_id = "";
+
+// Polls the tournament until it seats the player, then joins their table
+function wait_for_seat(player_id: string) {
+    const tournament_label = document.getElementById("tournament_label")!;
+    const start_tournament_button = document.getElementById("start_tournament_button")!;
+    api<TournamentViewState>(`/tournament?tournament_id=${current_tournament_id}`).then(tournament => {
+        const table_id = tournament.seating[player_id];
+        if (table_id !== undefined) {
+            current_table_id = `${table_id}`;
+            tournament_label.classList.add("hidden");
+            start_tournament_button.classList.add("hidden");
+            join();
+        } else {
+            tournament_label.innerText = tournament.started ? "The tournament has already started" : `${tournament.registered.length} players registered, waiting for the tournament to start`;
+            tournament_label.classList.remove("hidden");
+            start_tournament_button.classList.toggle("hidden", tournament.started);
+            if (!tournament.started) {
+                setTimeout(() => wait_for_seat(player_id), 2000);
+            }
+        }
+    }).catch(err => {
+        console.log(`err: ${err}`);
+    });
+}
+
+function register_tournament(player_id: string) {
+    // Registering again after a reload fails, but the player is still registered
+    fetch(`/register_tournament?tournament_id=${current_tournament_id}&player=${player_id}`, {
+        method: 'POST',
+        headers: auth_headers(),
+    }).then(() => {
+        wait_for_seat(player_id);
+    });
+}
+
+function start_tournament() {
+    fetch(`/start_tournament?tournament_id=${current_tournament_id}`, {
+        method: 'POST'
+    });
+}
+
 function join() {
     const player_input = <HTMLInputElement>document.getElementById("name_input");
     const player_id = player_input.value.trim();
+    if (current_tournament_id.length > 0 && current_table_id.length == 0) {
+        if (player_id.length > 0) {
+            register_tournament(player_id);
+        }
+        return;
+    }
 
     let player_id_q = player_id.length > 0 ? `player=${player_id}&` : "";
 
@@ -662,6 +865,58 @@ function replace() {
     }
 }
 
+function show_uncontested(resp: ShowResp) {
+    const player_input = document.getElementById("name_input")!;
+    const player_id = (<HTMLInputElement>player_input).value.trim();
+    const call_button = document.getElementById("call_button")!;
+    const fold_button = document.getElementById("fold_button")!;
+
+    show_uncontested_requested = false;
+    call_button.setAttribute("disabled", "");
+    fold_button.setAttribute("disabled", "");
+    const revert = () => {
+        show_uncontested_requested = true;
+        call_button.removeAttribute("disabled");
+        fold_button.removeAttribute("disabled");
+    };
+    fetch(`/show_uncontested?player=${player_id}&table_id=${current_table_id}`, {
+        method: "POST",
+        headers: auth_headers(),
+        body: JSON.stringify(resp)
+    }).then(resp => {
+        if (!resp.ok) {
+            revert();
+        }
+    }).catch(err => {
+        revert();
+    });
+}
+
+function answer_question(path: string, accept: boolean) {
+    const player_input = document.getElementById("name_input")!;
+    const player_id = (<HTMLInputElement>player_input).value.trim();
+    const call_button = document.getElementById("call_button")!;
+    const fold_button = document.getElementById("fold_button")!;
+
+    call_button.setAttribute("disabled", "");
+    fold_button.setAttribute("disabled", "");
+    const revert = () => {
+        call_button.removeAttribute("disabled");
+        fold_button.removeAttribute("disabled");
+    };
+    fetch(`/${path}?player=${player_id}&table_id=${current_table_id}`, {
+        method: "POST",
+        headers: auth_headers(),
+        body: JSON.stringify(accept)
+    }).then(resp => {
+        if (!resp.ok) {
+            revert();
+        }
+    }).catch(err => {
+        revert();
+    });
+}
+
 function bet(action: "bet" | "fold" | "call") {
     const player_input = document.getElementById("name_input")!;
     const player_id = (<HTMLInputElement>player_input).value.trim();
@@ -717,14 +972,26 @@ function start_server() {
     });
 }
 
+function stop_server() {
+    fetch(`/stop?table_id=${current_table_id}`, {
+        method: 'POST'
+    });
+}
+
 function add_bot(skill: number) {
-    fetch(`/add_bot?table_id=${current_table_id}&bot_skill=${skill}`, {
+    // Before a tournament starts, bots register for it instead
+    const at = (current_tournament_id.length > 0 && current_table_id.length == 0) ? `tournament_id=${current_tournament_id}` : `table_id=${current_table_id}`;
+    fetch(`/add_bot?${at}&bot_skill=${skill}`, {
         method: 'POST'
     });
 }
 
 function call_button_clicked() {
-    if (clicked_cards) {
+    if (question_requested) {
+        answer_question(question_requested, true);
+    } else if (show_uncontested_requested) {
+        show_uncontested({kind: "All"});
+    } else if (clicked_cards) {
         replace();
     } else {
         bet("call");
@@ -846,6 +1113,7 @@ function read_ante_rule(): AnteRuleDesc {
     const rounds_option = <HTMLInputElement>document.getElementById("bet_increase_rounds_option")!;
     const minutes_option = <HTMLInputElement>document.getElementById("bet_increase_minutes_option")!;
     const blinds_radio = <HTMLInputElement>document.getElementById("ante_blinds")!;
+    const bring_in_radio = <HTMLInputElement>document.getElementById("ante_bring_in")!;
 
     let change: AnteRuleChangeDesc = double_bet_input.checked ? (rounds_option.checked ? {
         kind: "MulEveryNRounds",
@@ -864,23 +1132,117 @@ function read_ante_rule(): AnteRuleDesc {
     let retval: AnteRuleDesc = {
         starting_value: Number.parseInt(min_bet_input.value),
         blinds: blinds_radio.checked,
+        bring_in: bring_in_radio.checked,
         change: change,
+        schedule: read_blind_schedule(),
     };
 
     return retval;
 }
 
-function create_table() {
-    const create_table_button = document.getElementById("create_table_button")!;
-    const table_settings_modal = document.getElementById("table_settings_modal")!;
+// Parses lines like "25 50 0 15m", "100 200 25 10h" or "break 10m". An ante like "200bb" is a big blind ante
+function read_blind_schedule(): BlindSchedule | null {
+    const schedule_input = <HTMLTextAreaElement>document.getElementById("blind_schedule_input")!;
+    const parse_duration = (s: string): LevelDuration => {
+        const value = Number.parseInt(s);
+        return s.endsWith("h") ? {kind: "Hands", data: value} : {kind: "Minutes", data: value};
+    };
+    let entries: Array<ScheduleEntry> = [];
+    for (const line of schedule_input.value.split("\n")) {
+        const words = line.trim().split(/\s+/);
+        if (words.length == 2 && words[0] == "break") {
+            entries.push({kind: "Break", data: {minutes: Number.parseInt(words[1])}});
+        } else if (words.length == 4) {
+            entries.push({kind: "Level", data: {
+                small_blind: Number.parseInt(words[0]),
+                big_blind: Number.parseInt(words[1]),
+                ante: Number.parseInt(words[2]),
+                big_blind_ante: words[2].endsWith("bb"),
+                duration: parse_duration(words[3]),
+            }});
+        }
+    }
+    return entries.length > 0 ? {entries: entries} : null;
+}
+
+var level_interval: number | null = null;
+function draw_level(level: LevelViewState | null) {
+    const level_label = document.getElementById("level_label")!;
+    if (level_interval != null) {
+        window.clearInterval(level_interval);
+        level_interval = null;
+    }
+    if (!level) {
+        level_label.innerHTML = "";
+        return;
+    }
+    const entry = level.entry;
+    const name = entry.kind == "Break" ? "Break" :
+        `Level ${level.level}: ${entry.data.small_blind}/${entry.data.big_blind}` + (entry.data.ante > 0 ? ` ${entry.data.big_blind_ante ? "big blind ante" : "ante"} ${entry.data.ante}` : "");
+    const received = Date.now();
+    const tick = () => {
+        let left = "";
+        if (level.secs_left != null) {
+            const secs = Math.max(0, level.secs_left - Math.floor((Date.now() - received) / 1000));
+            left = ` (${Math.floor(secs / 60)}:${String(secs % 60).padStart(2, "0")} left)`;
+        } else if (level.hands_left != null) {
+            left = ` (${level.hands_left} hands left)`;
+        }
+        level_label.innerHTML = name + left;
+    };
+    tick();
+    level_interval = window.setInterval(tick, 1000);
+}
+
+function read_betting_structure(): BettingStructure {
+    const betting_input = <HTMLSelectElement>document.getElementById("betting_structure_input")!;
+    const min_bet_input = <HTMLInputElement>document.getElementById("min_bet_input")!;
+    const blinds_radio = <HTMLInputElement>document.getElementById("ante_blinds")!;
+
+    switch (betting_input.value) {
+        case "pot_limit": {
+            return {kind: "PotLimit"};
+        }
+        case "fixed_limit": {
+            // The small bet is the big blind, and the big bet is twice that
+            const starting_value = Number.parseInt(min_bet_input.value);
+            const small_bet = blinds_radio.checked ? starting_value * 2 : starting_value;
+            return {
+                kind: "FixedLimit",
+                data: {
+                    small_bet: small_bet,
+                    big_bet: small_bet * 2,
+                    raise_cap: 4,
+                }
+            };
+        }
+        default: {
+            return {kind: "NoLimit"};
+        }
+    }
+}
+
+function read_prizes(): PrizeStructure {
+    const prizes_input = <HTMLInputElement>document.getElementById("prizes_input")!;
+    return {
+        percentages: prizes_input.value.split(",").map(p => Number.parseInt(p)).filter(p => p > 0),
+    };
+}
+
+function read_table_config(): TableConfig {
+    const sit_and_go_input = <HTMLInputElement>document.getElementById("sit_and_go_input")!;
     const max_players_input = <HTMLInputElement>document.getElementById("max_players_input")!;
+    const action_timer_input = <HTMLInputElement>document.getElementById("action_timer_input")!;
+    const time_bank_input = <HTMLInputElement>document.getElementById("time_bank_input")!;
+    const rebuy_amount_input = <HTMLInputElement>document.getElementById("rebuy_amount_input")!;
+    const max_rebuys_input = <HTMLInputElement>document.getElementById("max_rebuys_input")!;
+    const add_on_amount_input = <HTMLInputElement>document.getElementById("add_on_amount_input")!;
+    const add_on_until_input = <HTMLInputElement>document.getElementById("add_on_until_input")!;
     const starting_chips_input = <HTMLInputElement>document.getElementById("starting_chips_input")!;
     const dealers_choice_input = <HTMLInputElement>document.getElementById("dealers_choice_input")!;
     const rotation_input = <HTMLInputElement>document.getElementById("rotation_input")!;
     const included_list = document.getElementById("included_variant_list")!;
 
-    create_table_button.setAttribute("disabled", "");
-
     const special_card_template = document.getElementById("special_card_template")!;
     let special_card_names = [];
     for (const input of special_card_template.getElementsByClassName("special_card_settings_label")) {
@@ -910,17 +1272,105 @@ function create_table() {
         },
     };
 
+    const rebuy_amount = Number.parseInt(rebuy_amount_input.value);
+    const max_rebuys = Number.parseInt(max_rebuys_input.value);
+    const add_on_amount = Number.parseInt(add_on_amount_input.value);
+    const rebuys: RebuyRules = {
+        allowed: rebuy_amount > 0,
+        max_rebuys: max_rebuys > 0 ? max_rebuys : null,
+        amount: rebuy_amount,
+        add_on: add_on_amount > 0 ? {amount: add_on_amount, until_hand: Number.parseInt(add_on_until_input.value)} : null,
+    };
+
     let config: TableConfig = {
         max_players: Number.parseInt(max_players_input.value),
+        timer: {
+            action_secs: Number.parseInt(action_timer_input.value) > 0 ? Number.parseInt(action_timer_input.value) : null,
+            time_bank_secs: Number.parseInt(time_bank_input.value),
+        },
+        rebuys: rebuys,
         starting_chips: Number.parseInt(starting_chips_input.value),
         variant_selector: selector,
+        sit_and_go: sit_and_go_input.checked ? read_prizes() : null,
+    };
+
+    return config;
+}
+
+function read_rake(): Rake | null {
+    const rake_percent_input = <HTMLInputElement>document.getElementById("rake_percent_input")!;
+    const rake_cap_input = <HTMLInputElement>document.getElementById("rake_cap_input")!;
+    const no_flop_no_drop_input = <HTMLInputElement>document.getElementById("no_flop_no_drop_input")!;
+    const percent = Number.parseInt(rake_percent_input.value);
+    const cap = Number.parseInt(rake_cap_input.value);
+    if (!(percent > 0)) {
+        return null;
+    }
+    return {
+        percent: percent,
+        cap: cap > 0 ? cap : null,
+        no_flop_no_drop: no_flop_no_drop_input.checked,
+    };
+}
+
+function read_straddle(): StraddleRule | null {
+    const straddle_input = <HTMLSelectElement>document.getElementById("straddle_input")!;
+    switch (straddle_input.value) {
+        case "utg":
+            return {kind: "UnderTheGun"};
+        case "button":
+            return {kind: "Button"};
+        default:
+            return null;
+    }
+}
+
+function read_bomb_pot(): BombPotRule | null {
+    const bomb_pot_ante_input = <HTMLInputElement>document.getElementById("bomb_pot_ante_input")!;
+    const bomb_pot_every_input = <HTMLInputElement>document.getElementById("bomb_pot_every_input")!;
+    const bomb_pot_vote_input = <HTMLInputElement>document.getElementById("bomb_pot_vote_input")!;
+    const ante = Number.parseInt(bomb_pot_ante_input.value);
+    const every_hands = Number.parseInt(bomb_pot_every_input.value);
+    if (!(ante > 0)) {
+        return null;
+    }
+    return {
+        ante: ante,
+        every_hands: every_hands > 0 ? every_hands : null,
+        by_vote: bomb_pot_vote_input.checked,
     };
+}
+
+function read_run_it(): number | null {
+    const run_it_input = <HTMLInputElement>document.getElementById("run_it_input")!;
+    const times = Number.parseInt(run_it_input.value);
+    return times > 1 ? times : null;
+}
 
+function read_all_in_pause_ms(): number {
+    const all_in_pause_input = <HTMLInputElement>document.getElementById("all_in_pause_input")!;
+    const secs = Number.parseFloat(all_in_pause_input.value);
+    return secs > 0 ? Math.round(secs * 1000) : 0;
+}
+
+function create_table() {
+    const create_table_button = document.getElementById("create_table_button")!;
+    const table_settings_modal = document.getElementById("table_settings_modal")!;
+
+    create_table_button.setAttribute("disabled", "");
+
+    let config = read_table_config();
     console.log(config);
 
     let params: ServerTableParameters = {
         table_config: config,
         ante_rule: read_ante_rule(),
+        betting: read_betting_structure(),
+        rake: read_rake(),
+        straddle: read_straddle(),
+        bomb_pot: read_bomb_pot(),
+        run_it: read_run_it(),
+        all_in_pause_ms: read_all_in_pause_ms(),
     };
 
     fetch('/create_table', {
@@ -940,6 +1390,37 @@ function create_table() {
     });
 }
 
+function create_tournament() {
+    const create_tournament_button = document.getElementById("create_tournament_button")!;
+    const schedule = read_blind_schedule();
+    if (!schedule) {
+        return;
+    }
+
+    let config: TournamentConfig = {
+        // Tournament chips only come from buying in
+        table_config: {...read_table_config(), sit_and_go: null, rebuys: {allowed: false, max_rebuys: null, amount: 0, add_on: null}},
+        schedule: schedule,
+        betting: read_betting_structure(),
+        prizes: read_prizes(),
+    };
+
+    create_tournament_button.setAttribute("disabled", "");
+    fetch('/create_tournament', {
+        method: "POST",
+        body: JSON.stringify(config)
+    }).then(resp => {
+        create_tournament_button.removeAttribute("disabled");
+        if (resp.ok) {
+            resp.json().then(tournament_id => {
+                window.location.href = `table?tournament_id=${tournament_id}`;
+            });
+        }
+    }).catch(err => {
+        create_tournament_button.removeAttribute("disabled");
+    });
+}
+
 document.addEventListener('DOMContentLoaded', () => {
     read_auth_token_cookie();
 
@@ -953,13 +1434,22 @@ document.addEventListener('DOMContentLoaded', () => {
     const start_server_button = document.getElementById("start_server_button")!;
     const add_easy_bot_button = document.getElementById("add_easy_bot_button")!;
     const add_medium_bot_button = document.getElementById("add_medium_bot_button")!;
+    const stop_server_button = document.getElementById("stop_server_button")!;
     const add_variant_button = document.getElementById("settings_add_variant_button")!;
     const remove_variant_button = document.getElementById("settings_remove_variant_button")!;
     const table_settings_modal = document.getElementById("table_settings_modal")!;
     const create_table_button = document.getElementById("create_table_button")!;
+    const create_tournament_button = document.getElementById("create_tournament_button")!;
+    const start_tournament_button = document.getElementById("start_tournament_button")!;
     const rotation_input = <HTMLInputElement>document.getElementById("rotation_input")!;
     const dealers_choice_input = <HTMLInputElement>document.getElementById("dealers_choice_input")!;
     const dealers_choice_submit = <HTMLInputElement>document.getElementById("dealers_choice_submit")!;
+    const sit_in_button = document.getElementById("sit_in_button")!;
+    const sit_out_button = document.getElementById("sit_out_button")!;
+    const leave_button = document.getElementById("leave_button")!;
+    const rebuy_button = document.getElementById("rebuy_button")!;
+    const add_on_button = document.getElementById("add_on_button")!;
+    const bomb_pot_button = document.getElementById("bomb_pot_button")!;
 
     player_input.addEventListener('click', () => {
         join();
@@ -968,7 +1458,13 @@ document.addEventListener('DOMContentLoaded', () => {
         call_button_clicked();
     });
     fold_button.addEventListener('click', () => {
-        bet("fold");
+        if (question_requested) {
+            answer_question(question_requested, false);
+        } else if (show_uncontested_requested) {
+            show_uncontested({kind: "Muck"});
+        } else {
+            bet("fold");
+        }
     });
     bet_button.addEventListener('click', () => {
         bet("bet");
@@ -985,12 +1481,21 @@ document.addEventListener('DOMContentLoaded', () => {
     add_easy_bot_button.addEventListener('click', () => {
         add_bot(1);
     });
+    stop_server_button.addEventListener('click', () => {
+        stop_server();
+    });
     add_medium_bot_button.addEventListener('click', () => {
         add_bot(2);
     });
     create_table_button.addEventListener('click', () => {
         create_table();
     });
+    create_tournament_button.addEventListener('click', () => {
+        create_tournament();
+    });
+    start_tournament_button.addEventListener('click', () => {
+        start_tournament();
+    });
     rotation_input.addEventListener('change', () => {
         draw_settings_variants();
     });
@@ -1000,6 +1505,24 @@ document.addEventListener('DOMContentLoaded', () => {
     dealers_choice_submit.addEventListener('click', () => {
         dealers_choice();
     });
+    sit_in_button.addEventListener('click', () => {
+        seat_action("sit_in");
+    });
+    sit_out_button.addEventListener('click', () => {
+        seat_action("sit_out");
+    });
+    leave_button.addEventListener('click', () => {
+        seat_action("leave");
+    });
+    rebuy_button.addEventListener('click', () => {
+        seat_action("rebuy");
+    });
+    add_on_button.addEventListener('click', () => {
+        seat_action("add_on");
+    });
+    bomb_pot_button.addEventListener('click', () => {
+        seat_action("vote_bomb_pot");
+    });
 
     for (const ele of document.getElementsByClassName("settings_variant_button")) {
         const button = <HTMLInputElement>ele;
@@ -1010,9 +1533,13 @@ document.addEventListener('DOMContentLoaded', () => {
 
     const current_url = new URL(window.location.href);
     const table_id = current_url.searchParams.get("table_id");
+    const tournament_id = current_url.searchParams.get("tournament_id");
     if (table_id) {
         table_settings_modal.classList.add("hidden");
         current_table_id = table_id;
         join();
+    } else if (tournament_id) {
+        table_settings_modal.classList.add("hidden");
+        current_tournament_id = tournament_id;
     }
 });